@@ -0,0 +1,257 @@
+use crate::json_person::PersonClean;
+use crate::json_research::ResearchClean;
+use regex::RegexSet;
+use std::collections::{HashMap, HashSet};
+
+// Finds the abstracts and profile texts which are not real texts, but
+// placeholders ("[abstract missing]") or boilerplate (publisher copyright
+// notices), and removes them (sets them to None), so they do not pollute
+// the retrieval. Two checks:
+//
+//  - the text matches one of the patterns (the defaults below, plus the
+//    ones from a --boilerplate file),
+//  - the same text (after normalise()) is found in at least min_repeats
+//    records, a real abstract or profile text is not repeated.
+
+/// The built-in patterns, matched against the trimmed text.
+pub const DEFAULT_PATTERNS: &[&str] = &[
+    r"(?i)^\[?\s*abstracts?\s+(is\s+)?(missing|not\s+available|unavailable)\s*\]?\.?$",
+    r"(?i)^\[?\s*no\s+abstract(\s+available)?\s*\]?\.?$",
+    r"(?i)^there\s+is\s+no\s+profile_?information\.?$",
+    r"(?i)^(©|\(c\)|copyright)\s.*\ball\s+rights\s+reserved\.?$",
+    r"(?i)^(n/?a|-+|\.+|tbd|todo)$",
+];
+
+/// A text found in this many records is boilerplate.
+pub const DEFAULT_MIN_REPEATS: usize = 20;
+
+/// The number of texts removed, per check.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct BoilerplateCounts {
+    pub pattern: usize,
+    pub repeated: usize,
+}
+
+impl BoilerplateCounts {
+    pub fn total(&self) -> usize {
+        self.pattern + self.repeated
+    }
+}
+
+#[derive(Debug)]
+pub struct Boilerplate {
+    patterns: RegexSet,
+    // 0 switches the repeated text check off.
+    min_repeats: usize,
+}
+
+// Lower case words, without digits and punctuation, so the copyright
+// notices of different years are the same text.
+pub fn normalise(text: &str) -> String {
+    text.split(|c: char| !c.is_alphabetic())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// The abstracts (also the translations) of a research output.
+fn research_texts(research: &mut ResearchClean) -> Vec<&mut Option<String>> {
+    let mut texts = vec![&mut research.abstract_text];
+    texts.extend(
+        research
+            .translations
+            .values_mut()
+            .map(|texts| &mut texts.abstract_text),
+    );
+    texts
+}
+
+// The profile texts (also the translations) of a person.
+fn person_texts(person: &mut PersonClean) -> Vec<&mut Option<String>> {
+    let (profile_info, translations) = person.get_profile_texts_mut();
+    let mut texts = vec![profile_info];
+    texts.extend(
+        translations
+            .values_mut()
+            .map(|texts| &mut texts.profile_info),
+    );
+    texts
+}
+
+impl Boilerplate {
+    /// The default patterns plus the extra ones.
+    pub fn new(extra: &[String], min_repeats: usize) -> Result<Self, regex::Error> {
+        let patterns = DEFAULT_PATTERNS
+            .iter()
+            .map(|pattern| pattern.to_string())
+            .chain(extra.iter().cloned());
+        Ok(Boilerplate {
+            patterns: RegexSet::new(patterns)?,
+            min_repeats,
+        })
+    }
+
+    pub fn matches_pattern(&self, text: &str) -> bool {
+        self.patterns.is_match(text.trim())
+    }
+
+    // The normalised texts found in at least min_repeats records. Each
+    // record counts once, also when the text is in two locales.
+    fn repeated<'a>(&self, records: impl Iterator<Item = Vec<&'a str>>) -> HashSet<String> {
+        if self.min_repeats == 0 {
+            return HashSet::new();
+        }
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for texts in records {
+            let keys: HashSet<String> = texts
+                .into_iter()
+                .map(normalise)
+                .filter(|key| !key.is_empty())
+                .collect();
+            for key in keys {
+                *counts.entry(key).or_default() += 1;
+            }
+        }
+        counts
+            .into_iter()
+            .filter(|(_, count)| *count >= self.min_repeats)
+            .map(|(key, _)| key)
+            .collect()
+    }
+
+    // Sets the boilerplate texts to None.
+    fn strip_texts(
+        &self,
+        texts: Vec<&mut Option<String>>,
+        repeated: &HashSet<String>,
+        counts: &mut BoilerplateCounts,
+    ) {
+        for text in texts {
+            let Some(value) = text.as_deref() else {
+                continue;
+            };
+            if self.matches_pattern(value) {
+                counts.pattern += 1;
+            } else if repeated.contains(&normalise(value)) {
+                counts.repeated += 1;
+            } else {
+                continue;
+            }
+            *text = None;
+        }
+    }
+
+    /// Removes the boilerplate abstracts.
+    pub fn strip_research(
+        &self,
+        research: &mut HashMap<String, ResearchClean>,
+    ) -> BoilerplateCounts {
+        let repeated = self.repeated(research.values().map(|item| {
+            std::iter::once(item.get_abstract())
+                .chain(
+                    item.translations
+                        .values()
+                        .map(|t| t.abstract_text.as_deref()),
+                )
+                .flatten()
+                .collect()
+        }));
+        let mut counts = BoilerplateCounts::default();
+        for item in research.values_mut() {
+            self.strip_texts(research_texts(item), &repeated, &mut counts);
+        }
+        counts
+    }
+
+    /// Removes the boilerplate profile texts.
+    pub fn strip_persons(&self, persons: &mut HashMap<String, PersonClean>) -> BoilerplateCounts {
+        let repeated = self.repeated(persons.values().map(|person| {
+            std::iter::once(person.get_profile_info())
+                .chain(
+                    person
+                        .get_translations()
+                        .values()
+                        .map(|t| t.profile_info.as_deref()),
+                )
+                .flatten()
+                .collect()
+        }));
+        let mut counts = BoilerplateCounts::default();
+        for person in persons.values_mut() {
+            self.strip_texts(person_texts(person), &repeated, &mut counts);
+        }
+        counts
+    }
+}
+
+// ===========================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_patterns() {
+        let boilerplate = Boilerplate::new(&["(?i)^lorem ipsum".to_string()], 0).unwrap();
+        for text in [
+            "[abstract missing]",
+            " Abstract not available. ",
+            "No abstract available",
+            "There is no profile_information.",
+            "© 2021 Elsevier B.V. All rights reserved.",
+            "Lorem ipsum dolor sit amet.",
+            "n/a",
+        ] {
+            assert!(boilerplate.matches_pattern(text), "{:?}", text);
+        }
+        for text in [
+            "We study the missing abstracts in Pure.",
+            "Results are robust. © 2021 Elsevier B.V. All rights reserved. See the appendix.",
+            "",
+        ] {
+            assert!(!boilerplate.matches_pattern(text), "{:?}", text);
+        }
+        assert_eq!(
+            normalise("© 2019 Springer-Verlag."),
+            normalise("© 2020, springer verlag")
+        );
+    }
+
+    #[test]
+    fn test_strip_research() {
+        let mut research: HashMap<String, ResearchClean> = HashMap::new();
+        for n in 0..4 {
+            let notice = format!("This chapter is published by Publisher {}, Inc.", 2000 + n);
+            let rc = ResearchClean::builder(format!("uuid-{}", n))
+                .abstract_text(notice)
+                .build();
+            research.insert(rc.get_uuid().to_string(), rc);
+        }
+        for (uuid, text) in [
+            ("uuid-a", "[abstract missing]"),
+            ("uuid-b", "A real abstract."),
+        ] {
+            let rc = ResearchClean::builder(uuid).abstract_text(text).build();
+            research.insert(uuid.to_string(), rc);
+        }
+        research.insert(
+            "uuid-c".to_string(),
+            ResearchClean::builder("uuid-c").build(),
+        );
+
+        let boilerplate = Boilerplate::new(&[], 4).unwrap();
+        let counts = boilerplate.strip_research(&mut research);
+        assert_eq!(
+            counts,
+            BoilerplateCounts {
+                pattern: 1,
+                repeated: 4
+            }
+        );
+        assert_eq!(research["uuid-0"].get_abstract(), None);
+        assert_eq!(research["uuid-a"].get_abstract(), None);
+        assert_eq!(research["uuid-b"].get_abstract(), Some("A real abstract."));
+        assert_eq!(research["uuid-c"].get_abstract(), None);
+    }
+}
@@ -1,6 +1,8 @@
 use std::collections::HashMap;
 
 use crate::errors::CombinedError;
+use crate::optout::OptOutPolicy;
+use crate::org_tree::OrgTree;
 use crate::PersonClean;
 use crate::ResearchClean;
 use std::fmt;
@@ -12,13 +14,21 @@ use std::fmt;
 // in PersonClean we have research (or uuids?).
 //
 // Probably better "solved" in a relational DB.
+//
+// All the keys are safe uuids: research is keyed by the safe research uuid,
+// persons by the safe person uuid, and person_research maps a safe person
+// uuid to the safe uuids of the research it is part of. The person_research
+// index is built from the PersonRefs in the research, so it has to be
+// rebuilt (reindex()) when the research is filtered. The orgtree is kept
+// for the orgunit filter, it is empty without an orgunits file.
 
 #[derive(Debug)]
 pub struct Combined {
     pub research: HashMap<String, ResearchClean>,
     pub persons: HashMap<String, PersonClean>,
     pub person_research: HashMap<String, Vec<String>>,
-    pub optout: Vec<String>,
+    pub optout: OptOutPolicy,
+    pub orgtree: OrgTree,
 }
 
 impl fmt::Display for Combined {
@@ -40,15 +50,46 @@ impl Combined {
     pub fn new(
         research: HashMap<String, ResearchClean>,
         persons: HashMap<String, PersonClean>,
-        person_research: HashMap<String, Vec<String>>,
-        optout: Vec<String>,
+        optout: OptOutPolicy,
     ) -> Self {
-        Self {
+        let mut combined = Self {
             research,
             persons,
-            person_research,
+            person_research: HashMap::new(),
             optout,
+            orgtree: OrgTree::default(),
+        };
+        combined.reindex();
+        combined
+    }
+
+    // (Re)builds the person -> [research, ...] index from the persons
+    // in the research. The research uuids are sorted so the index is
+    // the same between runs.
+    pub fn reindex(&mut self) {
+        let mut person_research: HashMap<String, Vec<String>> = HashMap::new();
+        for (research_uuid, research) in &self.research {
+            for p in &research.persons {
+                person_research
+                    .entry(p.uuid.clone())
+                    .or_default()
+                    .push(research_uuid.clone());
+            }
         }
+        for research_uuids in person_research.values_mut() {
+            research_uuids.sort();
+            research_uuids.dedup();
+        }
+        self.person_research = person_research;
+    }
+
+    // Keeps the research for which f returns true, and updates the index.
+    pub fn retain_research<F>(&mut self, f: F)
+    where
+        F: FnMut(&String, &mut ResearchClean) -> bool,
+    {
+        self.research.retain(f);
+        self.reindex();
     }
 
     // What should we return? String? JSONL?
@@ -98,6 +139,21 @@ impl Combined {
         Ok((research, persons))
     }
 
+    // Name of a person, from the persons data if present, otherwise from
+    // the research (external persons are only named there).
+    pub fn get_person_name(&self, uuid: &str) -> Option<&str> {
+        if let Some(person) = self.persons.get(uuid) {
+            return Some(person.get_name());
+        }
+        let research_uuid = self.person_research.get(uuid)?.first()?;
+        self.research
+            .get(research_uuid)?
+            .persons
+            .iter()
+            .find(|p| p.uuid == uuid)
+            .map(|p| p.get_name())
+    }
+
     // Return all the uuids in the research HashMap. If empty we
     // return an empty vector.
     pub fn get_all_research_uuids(&self) -> Vec<&String> {
@@ -123,9 +179,8 @@ impl Combined {
         }
     }
 
-    // Get all ResearchClean articles where uuid is one of the authors.
-    // (Probably only internal.)
-    pub fn _get_research_for_person_uuid(
+    // Get all ResearchClean articles where (safe) uuid is one of the authors.
+    pub fn get_research_for_person_uuid(
         &self,
         uuid: &str,
     ) -> Result<Vec<&ResearchClean>, CombinedError> {
@@ -143,3 +198,101 @@ impl Combined {
         Ok(research)
     }
 }
+
+// ===========================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::Rejects;
+    use crate::json_person::read_persons_jsonl;
+    use crate::json_research::read_research_jsonl;
+    use crate::uuid_map::UuidMap;
+    use std::path::{Path, PathBuf};
+
+    fn make_test_path(file_name: &str) -> PathBuf {
+        let project_root = env!("CARGO_MANIFEST_DIR");
+        let data_path = Path::new(project_root)
+            .join("tests")
+            .join("data")
+            .join(file_name);
+        data_path
+    }
+
+    // Reads the pjb_* test files the same way main() does, with one umap.
+    fn make_combined(umap: &mut UuidMap) -> Combined {
+        let research_path = make_test_path("pjb_research.jsonl");
+        let research_json = read_research_jsonl(
+            research_path.to_str().expect("Invalid path."),
+            &mut Rejects::default(),
+        )
+        .expect("Failed to read research data.");
+        let research: HashMap<String, ResearchClean> = research_json
+            .iter()
+            .map(|r| {
+                let rc = ResearchClean::try_from_with_locale_umap(r, &"en_GB".into(), umap)
+                    .expect("Failed to create ResearchClean");
+                (rc.get_uuid().to_string(), rc)
+            })
+            .collect();
+
+        let persons_path = make_test_path("pjb_persons.jsonl");
+        let persons_json = read_persons_jsonl(
+            persons_path.to_str().expect("Invalid path."),
+            &mut Rejects::default(),
+        )
+        .expect("Failed to read persons data.");
+        let persons: HashMap<String, PersonClean> = persons_json
+            .iter()
+            .map(|p| {
+                let pc = PersonClean::try_from_with_locale_umap(p, &"en_GB".into(), umap)
+                    .expect("Failed to create PersonClean");
+                (pc.get_uuid().to_string(), pc)
+            })
+            .collect();
+
+        Combined::new(research, persons, OptOutPolicy::default())
+    }
+
+    #[test]
+    fn test_person_research_join() {
+        let mut umap = UuidMap::new();
+        let combined = make_combined(&mut umap);
+        assert_eq!(combined.research.len(), 1);
+        assert_eq!(combined.persons.len(), 1);
+
+        // Peter Berck, internal person in both files.
+        let person_uuid = umap.get_uuid_as_str("6ed28bcf-7127-4ad9-8120-96eecf60f57c");
+        assert!(combined.persons.contains_key(&person_uuid));
+        let research = combined
+            .get_research_for_person_uuid(&person_uuid)
+            .expect("No research for person.");
+        assert_eq!(research.len(), 1);
+        assert_eq!(research[0].get_title(), "Memory-based Language Models");
+
+        // And back, from the research to the persons.
+        let research_uuid = umap.get_uuid_as_str("a6723008-96f5-414f-8ad3-7f4674c2e745");
+        let (research, persons) = combined
+            .get_research_from_uuid_ref(&research_uuid)
+            .expect("No such research.");
+        assert_eq!(research.get_title(), "Memory-based Language Models");
+        assert_eq!(persons.len(), 1);
+        assert_eq!(persons[0].get_name(), "Peter Berck");
+
+        // Original uuids are not keys.
+        assert!(combined
+            .get_research_for_person_uuid("6ed28bcf-7127-4ad9-8120-96eecf60f57c")
+            .is_err());
+    }
+
+    #[test]
+    fn test_reindex_after_filter() {
+        let mut umap = UuidMap::new();
+        let mut combined = make_combined(&mut umap);
+        let person_uuid = umap.get_uuid_as_str("6ed28bcf-7127-4ad9-8120-96eecf60f57c");
+
+        combined.retain_research(|_, r| r.get_title() != "Memory-based Language Models");
+        assert!(combined.research.is_empty());
+        assert!(combined.get_research_for_person_uuid(&person_uuid).is_err());
+    }
+}
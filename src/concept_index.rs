@@ -0,0 +1,167 @@
+use crate::json_concepts::ConceptJson;
+use crate::json_fingerprint::{Concept, FingerprintJson};
+use log::{debug, info};
+use serde::Serialize;
+use std::collections::HashMap;
+
+// Connects the fingerprints (content uuid -> concept uuids with weights) to
+// the concept labels. The content uuids are the original LUCRIS uuids of
+// research outputs or persons, so the lookup has to be done before the
+// uuids are replaced by safe uuids.
+
+/// A concept label with its weightedRank, as it ends up in the output.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct WeightedConcept {
+    pub label: String,
+    pub weight: f64,
+}
+
+/// Which concepts to keep: at most `top_n`, and only the ones with a
+/// weightedRank of at least `min_weight`.
+#[derive(Debug, Clone, Copy)]
+pub struct ConceptOptions {
+    pub top_n: usize,
+    pub min_weight: f64,
+}
+
+impl Default for ConceptOptions {
+    fn default() -> Self {
+        Self {
+            top_n: 10,
+            min_weight: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ConceptIndex {
+    labels: HashMap<String, String>,
+    fingerprints: HashMap<String, Vec<Concept>>,
+    options: ConceptOptions,
+}
+
+impl ConceptIndex {
+    pub fn new(
+        fingerprints: Vec<FingerprintJson>,
+        concepts: &[ConceptJson],
+        locale: &str,
+        options: ConceptOptions,
+    ) -> Self {
+        let labels: HashMap<String, String> = concepts
+            .iter()
+            .filter_map(|c| c.id_and_text_for_locale(locale))
+            .collect();
+        let fingerprints: HashMap<String, Vec<Concept>> = fingerprints
+            .iter()
+            .filter_map(|fp| fp.id_and_concepts())
+            .collect();
+        info!(
+            "Concept index with {} labels and {} fingerprints.",
+            labels.len(),
+            fingerprints.len()
+        );
+        Self {
+            labels,
+            fingerprints,
+            options,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fingerprints.is_empty()
+    }
+
+    /// The top concepts for a research output or person (original uuid),
+    /// highest weightedRank first. Concepts without a label are skipped.
+    pub fn top_concepts(&self, uuid: &str) -> Vec<WeightedConcept> {
+        let Some(concepts) = self.fingerprints.get(uuid) else {
+            return vec![];
+        };
+        let mut weighted: Vec<WeightedConcept> = concepts
+            .iter()
+            .filter_map(|c| {
+                let weight = c.weightedRank?;
+                if weight < self.options.min_weight {
+                    return None;
+                }
+                let concept_uuid = c.uuid.as_deref()?;
+                match self.labels.get(concept_uuid) {
+                    Some(label) => Some(WeightedConcept {
+                        label: label.clone(),
+                        weight,
+                    }),
+                    None => {
+                        debug!("No label for concept {}.", concept_uuid);
+                        None
+                    }
+                }
+            })
+            .collect();
+        weighted.sort_by(|a, b| {
+            b.weight
+                .total_cmp(&a.weight)
+                .then_with(|| a.label.cmp(&b.label))
+        });
+        weighted.truncate(self.options.top_n);
+        weighted
+    }
+}
+
+// ===========================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_index(options: ConceptOptions) -> ConceptIndex {
+        let fingerprint = r#"{"contentUuid":"research-1","concepts":[
+            {"uuid":"c1","weightedRank":0.2},
+            {"uuid":"c2","weightedRank":0.9},
+            {"uuid":"c3","weightedRank":0.5},
+            {"uuid":"c4","weightedRank":0.7}]}"#;
+        let concepts = [
+            r#"{"uuid":"c1","name":{"text":[{"locale":"en_GB","value":"Linguistics"}]}}"#,
+            r#"{"uuid":"c2","name":{"text":[{"locale":"en_GB","value":"Language models"},{"locale":"sv_SE","value":"Språkmodeller"}]}}"#,
+            r#"{"uuid":"c3","name":{"text":[{"locale":"en_GB","value":"Memory"}]}}"#,
+        ];
+        let fingerprints = vec![serde_json::from_str(fingerprint).unwrap()];
+        let concepts: Vec<ConceptJson> = concepts
+            .iter()
+            .map(|c| serde_json::from_str(c).unwrap())
+            .collect();
+        ConceptIndex::new(fingerprints, &concepts, "en_GB", options)
+    }
+
+    #[test]
+    fn test_top_concepts() {
+        let index = make_index(ConceptOptions::default());
+        let labels: Vec<String> = index
+            .top_concepts("research-1")
+            .into_iter()
+            .map(|c| c.label)
+            .collect();
+        // c4 has no label.
+        assert_eq!(labels, vec!["Language models", "Memory", "Linguistics"]);
+        assert!(index.top_concepts("research-2").is_empty());
+    }
+
+    #[test]
+    fn test_top_concepts_cutoff() {
+        let index = make_index(ConceptOptions {
+            top_n: 1,
+            min_weight: 0.0,
+        });
+        assert_eq!(index.top_concepts("research-1").len(), 1);
+
+        let index = make_index(ConceptOptions {
+            top_n: 10,
+            min_weight: 0.4,
+        });
+        let labels: Vec<String> = index
+            .top_concepts("research-1")
+            .into_iter()
+            .map(|c| c.label)
+            .collect();
+        assert_eq!(labels, vec!["Language models", "Memory"]);
+    }
+}
@@ -0,0 +1,584 @@
+use crate::combined::Combined;
+use crate::json_research::ResearchClean;
+use crate::names::{fold_text, normalize_name};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+
+// Finds the research outputs which are the same work (preprint,
+// conference and journal version, or entered twice by different
+// departments), and merges each cluster into one canonical record.
+//
+// Two research outputs are duplicates when they share a DOI, an external
+// id, or an ISBN (not for chapters and conference papers, which share
+// the ISBN of their book), or when their titles are similar and their
+// authors overlap. The similar titles are found with MinHash over the
+// character trigrams of the folded titles, with LSH banding, and then
+// checked with the exact Jaccard similarity. The duplicates are clustered
+// transitively (union-find).
+
+/// Which record of a cluster is kept.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum DedupPolicy {
+    /// The peer reviewed journal version, then the newest.
+    #[default]
+    Published,
+    /// The newest publication date.
+    Newest,
+    /// The one with an abstract and the most keywords and persons.
+    Complete,
+}
+
+/// Why a record is in a cluster (the first link found).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateReason {
+    Doi,
+    Isbn,
+    ExternalId,
+    Title,
+}
+
+impl fmt::Display for DuplicateReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match self {
+            DuplicateReason::Doi => "doi",
+            DuplicateReason::Isbn => "isbn",
+            DuplicateReason::ExternalId => "external_id",
+            DuplicateReason::Title => "title",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+/// Default minimum Jaccard similarity of the title trigrams.
+pub const TITLE_THRESHOLD: f64 = 0.8;
+/// Default minimum author overlap (shared / fewest authors).
+pub const AUTHOR_THRESHOLD: f64 = 0.5;
+/// Shorter (folded) titles are not compared ("Introduction", "Preface").
+pub const MIN_TITLE_CHARS: usize = 20;
+
+// MinHash signature length, in BANDS bands of ROWS rows.
+const BANDS: usize = 8;
+const ROWS: usize = 4;
+
+#[derive(Debug, Clone, Copy)]
+pub struct DedupOptions {
+    pub policy: DedupPolicy,
+    pub title_threshold: f64,
+    pub author_threshold: f64,
+}
+
+impl Default for DedupOptions {
+    fn default() -> Self {
+        DedupOptions {
+            policy: DedupPolicy::default(),
+            title_threshold: TITLE_THRESHOLD,
+            author_threshold: AUTHOR_THRESHOLD,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Duplicate {
+    pub uuid: String,
+    pub reason: DuplicateReason,
+    pub title: String,
+}
+
+/// A canonical record and the duplicates merged into it.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Cluster {
+    pub canonical: String,
+    pub title: String,
+    pub duplicates: Vec<Duplicate>,
+}
+
+// Union-find over the record indices. The reason is kept for the first
+// link of each record.
+struct Clusters {
+    parent: Vec<usize>,
+    reasons: Vec<Option<DuplicateReason>>,
+}
+
+impl Clusters {
+    fn new(size: usize) -> Self {
+        Clusters {
+            parent: (0..size).collect(),
+            reasons: vec![None; size],
+        }
+    }
+
+    fn find(&mut self, i: usize) -> usize {
+        let mut root = i;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        let mut i = i;
+        while self.parent[i] != root {
+            let next = self.parent[i];
+            self.parent[i] = root;
+            i = next;
+        }
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize, reason: DuplicateReason) {
+        let (root_a, root_b) = (self.find(a), self.find(b));
+        if root_a == root_b {
+            return;
+        }
+        self.parent[root_b.max(root_a)] = root_a.min(root_b);
+        for i in [a, b] {
+            self.reasons[i].get_or_insert(reason);
+        }
+    }
+}
+
+// Chapters and conference papers have the ISBN of the book.
+fn has_own_isbn(research: &ResearchClean) -> bool {
+    let research_type = research.get_research_type();
+    !(research_type.starts_with("contributionto") || research_type.starts_with("chapterinbook"))
+}
+
+// The character trigrams of the folded title.
+fn trigrams(title: &str) -> HashSet<String> {
+    let chars: Vec<char> = title.chars().collect();
+    chars
+        .windows(3)
+        .map(|window| window.iter().collect())
+        .collect()
+}
+
+fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
+fn hash<T: Hash>(value: T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn minhash(shingles: &HashSet<String>) -> Vec<u64> {
+    (0..BANDS * ROWS)
+        .map(|seed| {
+            shingles
+                .iter()
+                .map(|shingle| hash((seed, shingle)))
+                .min()
+                .unwrap_or(0)
+        })
+        .collect()
+}
+
+// The shared authors divided by the number of authors of the one with the
+// fewest. Without authors on either side, only the title counts.
+fn author_overlap(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    match (a.is_empty(), b.is_empty()) {
+        (true, true) => 1.0,
+        (true, false) | (false, true) => 0.0,
+        _ => a.intersection(b).count() as f64 / a.len().min(b.len()) as f64,
+    }
+}
+
+fn authors(research: &ResearchClean) -> HashSet<String> {
+    research
+        .persons
+        .iter()
+        .map(|person| normalize_name(person.get_name()))
+        .collect()
+}
+
+// Joins the records which share an identifier.
+fn link_identifiers(records: &[&ResearchClean], clusters: &mut Clusters) {
+    let mut seen: HashMap<(DuplicateReason, &str), usize> = HashMap::new();
+    for (i, research) in records.iter().enumerate() {
+        let identifiers = &research.identifiers;
+        let dois = identifiers
+            .dois
+            .iter()
+            .map(|doi| (DuplicateReason::Doi, doi.as_str()));
+        let isbns = identifiers
+            .isbns
+            .iter()
+            .filter(|_| has_own_isbn(research))
+            .map(|isbn| (DuplicateReason::Isbn, isbn.as_str()));
+        let external_id = identifiers
+            .external_id
+            .iter()
+            .map(|id| (DuplicateReason::ExternalId, id.as_str()));
+        for key in dois.chain(isbns).chain(external_id) {
+            match seen.get(&key) {
+                Some(&first) => clusters.union(first, i, key.0),
+                None => {
+                    seen.insert(key, i);
+                }
+            }
+        }
+    }
+}
+
+// Joins the records with similar titles and overlapping authors.
+fn link_titles(records: &[&ResearchClean], options: &DedupOptions, clusters: &mut Clusters) {
+    let shingles: Vec<HashSet<String>> = records
+        .iter()
+        .map(|research| {
+            let title = fold_text(research.get_title());
+            if title.chars().count() < MIN_TITLE_CHARS {
+                HashSet::new()
+            } else {
+                trigrams(&title)
+            }
+        })
+        .collect();
+    let authors: Vec<HashSet<String>> = records.iter().map(|research| authors(research)).collect();
+
+    let mut buckets: HashMap<(usize, u64), Vec<usize>> = HashMap::new();
+    for (i, shingles) in shingles.iter().enumerate() {
+        if shingles.is_empty() {
+            continue;
+        }
+        let signature = minhash(shingles);
+        for (band, rows) in signature.chunks(ROWS).enumerate() {
+            buckets.entry((band, hash(rows))).or_default().push(i);
+        }
+    }
+
+    let mut checked: HashSet<(usize, usize)> = HashSet::new();
+    let mut bucket_keys: Vec<&(usize, u64)> = buckets.keys().collect();
+    bucket_keys.sort();
+    for key in bucket_keys {
+        let bucket = &buckets[key];
+        for (n, &a) in bucket.iter().enumerate() {
+            for &b in &bucket[n + 1..] {
+                if !checked.insert((a, b)) {
+                    continue;
+                }
+                if jaccard(&shingles[a], &shingles[b]) >= options.title_threshold
+                    && author_overlap(&authors[a], &authors[b]) >= options.author_threshold
+                {
+                    clusters.union(a, b, DuplicateReason::Title);
+                }
+            }
+        }
+    }
+}
+
+// Journal articles first, preprints and working papers last.
+fn type_rank(research_type: &str) -> u8 {
+    match research_type.split('/').next().unwrap_or("") {
+        "contributiontojournal" => 3,
+        "book" | "bookanthology" => 2,
+        "workingpaper" => 0,
+        _ if research_type.contains("preprint") => 0,
+        _ => 1,
+    }
+}
+
+// Greater is better.
+fn compare(a: &ResearchClean, b: &ResearchClean, policy: DedupPolicy) -> Ordering {
+    match policy {
+        DedupPolicy::Published => (
+            a.get_peer_review() == Some(true),
+            type_rank(a.get_research_type()),
+            a.get_publication_date(),
+        )
+            .cmp(&(
+                b.get_peer_review() == Some(true),
+                type_rank(b.get_research_type()),
+                b.get_publication_date(),
+            )),
+        DedupPolicy::Newest => a.get_publication_date().cmp(b.get_publication_date()),
+        DedupPolicy::Complete => (
+            a.get_abstract().is_some(),
+            a.keywords.len() + a.persons.len(),
+        )
+            .cmp(&(
+                b.get_abstract().is_some(),
+                b.keywords.len() + b.persons.len(),
+            )),
+    }
+}
+
+/// Finds the clusters of duplicates, with the canonical record chosen by
+/// the policy (the smallest uuid on ties). Sorted on canonical uuid.
+pub fn find_duplicates(
+    research: &HashMap<String, ResearchClean>,
+    options: &DedupOptions,
+) -> Vec<Cluster> {
+    let mut records: Vec<&ResearchClean> = research.values().collect();
+    records.sort_by(|a, b| a.get_uuid().cmp(b.get_uuid()));
+
+    let mut clusters = Clusters::new(records.len());
+    link_identifiers(&records, &mut clusters);
+    link_titles(&records, options, &mut clusters);
+
+    let mut members: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..records.len() {
+        let root = clusters.find(i);
+        members.entry(root).or_default().push(i);
+    }
+
+    let mut result: Vec<Cluster> = members
+        .into_values()
+        .filter(|members| members.len() > 1)
+        .map(|members| {
+            // The members are in uuid order, so the first best one wins.
+            let canonical = *members
+                .iter()
+                .reduce(
+                    |best, i| match compare(records[*i], records[*best], options.policy) {
+                        Ordering::Greater => i,
+                        _ => best,
+                    },
+                )
+                .expect("A cluster has members.");
+            Cluster {
+                canonical: records[canonical].get_uuid().to_string(),
+                title: records[canonical].get_title().to_string(),
+                duplicates: members
+                    .iter()
+                    .filter(|i| **i != canonical)
+                    .map(|i| Duplicate {
+                        uuid: records[*i].get_uuid().to_string(),
+                        reason: clusters.reasons[*i].unwrap_or(DuplicateReason::Title),
+                        title: records[*i].get_title().to_string(),
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+    result.sort_by(|a, b| a.canonical.cmp(&b.canonical));
+    result
+}
+
+// Adds what the canonical record is missing from a duplicate: the
+// abstract, keywords, persons, identifiers and venue.
+fn merge(canonical: &mut ResearchClean, duplicate: ResearchClean) {
+    canonical.duplicates.push(duplicate.get_uuid().to_string());
+    if canonical.abstract_text.is_none() {
+        canonical.abstract_text = duplicate.abstract_text;
+    }
+    for keyword in duplicate.keywords {
+        if !canonical.keywords.contains(&keyword) {
+            canonical.keywords.push(keyword);
+        }
+    }
+    // On uuid when both have one (the external persons need not), and
+    // otherwise on the name.
+    let mut names = authors(canonical);
+    for person in duplicate.persons {
+        let known =
+            !person.uuid.is_empty() && canonical.persons.iter().any(|p| p.uuid == person.uuid);
+        if !known && names.insert(normalize_name(person.get_name())) {
+            canonical.persons.push(person);
+        }
+    }
+    canonical.identifiers.merge(&duplicate.identifiers);
+    if canonical.venue.is_empty() {
+        canonical.venue = duplicate.venue;
+    }
+}
+
+/// Merges the duplicates into their canonical record, and removes them.
+pub fn merge_duplicates(research: &mut HashMap<String, ResearchClean>, clusters: &[Cluster]) {
+    for cluster in clusters {
+        for duplicate in &cluster.duplicates {
+            let Some(duplicate) = research.remove(&duplicate.uuid) else {
+                continue;
+            };
+            if let Some(canonical) = research.get_mut(&cluster.canonical) {
+                merge(canonical, duplicate);
+            }
+        }
+    }
+}
+
+/// Finds and merges the duplicate research in Combined, and updates the
+/// person -> research index. Returns the clusters.
+pub fn dedup_research(combined: &mut Combined, options: &DedupOptions) -> Vec<Cluster> {
+    let clusters = find_duplicates(&combined.research, options);
+    merge_duplicates(&mut combined.research, &clusters);
+    combined.reindex();
+    clusters
+}
+
+/// Writes the clusters as TSV, one line per duplicate.
+pub fn write_tsv(clusters: &[Cluster], mut output: impl Write) -> std::io::Result<()> {
+    writeln!(
+        output,
+        "canonical\tduplicate\treason\tcanonical_title\tduplicate_title"
+    )?;
+    for cluster in clusters {
+        for duplicate in &cluster.duplicates {
+            writeln!(
+                output,
+                "{}\t{}\t{}\t{}\t{}",
+                cluster.canonical,
+                duplicate.uuid,
+                duplicate.reason,
+                cluster.title.replace(['\t', '\n'], " "),
+                duplicate.title.replace(['\t', '\n'], " ")
+            )?;
+        }
+    }
+    output.flush()
+}
+
+// ===========================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json_research::{Identifiers, PersonRef};
+
+    fn research(uuid: &str, title: &str, authors: &[&str]) -> ResearchClean {
+        ResearchClean::builder(uuid)
+            .title(title)
+            .persons(
+                authors
+                    .iter()
+                    .enumerate()
+                    .map(|(i, name)| PersonRef::internal(i as u32, *name, *name))
+                    .collect(),
+            )
+            .build()
+    }
+
+    fn insert(map: &mut HashMap<String, ResearchClean>, research: ResearchClean) {
+        map.insert(research.get_uuid().to_string(), research);
+    }
+
+    #[test]
+    fn test_find_and_merge() {
+        let mut map = HashMap::new();
+        // Preprint and journal version, slightly different titles.
+        let preprint = ResearchClean::builder("uuid-1")
+            .title("Memory-based language models: an efficient alternative")
+            .abstract_text("We present memory-based language models.")
+            .keywords(vec!["language models".to_string()])
+            .research_type("workingpaper/preprint")
+            .publication_date("2024")
+            .persons(vec![
+                PersonRef::internal(0, "p1", "Peter Berck"),
+                PersonRef::internal(1, "p2", "Antal van den Bosch"),
+            ])
+            .build();
+        let journal = ResearchClean::builder("uuid-2")
+            .title("Memory-based Language Models: An Efficient Alternative.")
+            .keywords(vec!["memory-based learning".to_string()])
+            .research_type("contributiontojournal/article")
+            .peer_review(Some(true))
+            .publication_date("2025")
+            .persons(vec![PersonRef::internal(0, "p1", "Berck, Peter")])
+            .build();
+        insert(&mut map, preprint);
+        insert(&mut map, journal);
+        // Same title, other authors.
+        insert(
+            &mut map,
+            research(
+                "uuid-3",
+                "Memory-based language models: an efficient alternative",
+                &["Someone Else"],
+            ),
+        );
+        // Same DOI, different title.
+        let doi = Identifiers {
+            dois: vec!["10.1000/xyz".to_string()],
+            ..Identifiers::default()
+        };
+        for uuid in ["uuid-4", "uuid-5"] {
+            insert(
+                &mut map,
+                ResearchClean::builder(uuid)
+                    .title(format!("Title of {}", uuid))
+                    .identifiers(doi.clone())
+                    .build(),
+            );
+        }
+        // Chapters in the same book.
+        let isbn = Identifiers {
+            isbns: vec!["9789177531234".to_string()],
+            ..Identifiers::default()
+        };
+        for uuid in ["uuid-6", "uuid-7"] {
+            insert(
+                &mut map,
+                ResearchClean::builder(uuid)
+                    .title(format!("Chapter {}", uuid))
+                    .research_type("contributiontobookanthology/chapter")
+                    .identifiers(isbn.clone())
+                    .build(),
+            );
+        }
+        // Short titles are not compared.
+        insert(&mut map, research("uuid-8", "Introduction", &["A B"]));
+        insert(&mut map, research("uuid-9", "Introduction", &["A B"]));
+
+        let clusters = find_duplicates(&map, &DedupOptions::default());
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters[0].canonical, "uuid-2");
+        assert_eq!(clusters[0].duplicates[0].uuid, "uuid-1");
+        assert_eq!(clusters[0].duplicates[0].reason, DuplicateReason::Title);
+        assert_eq!(clusters[1].canonical, "uuid-4");
+        assert_eq!(clusters[1].duplicates[0].reason, DuplicateReason::Doi);
+
+        let options = DedupOptions {
+            policy: DedupPolicy::Complete,
+            ..DedupOptions::default()
+        };
+        assert_eq!(find_duplicates(&map, &options)[0].canonical, "uuid-1");
+
+        merge_duplicates(&mut map, &clusters);
+        assert_eq!(map.len(), 7);
+        let merged = &map["uuid-2"];
+        assert_eq!(
+            merged.get_abstract(),
+            Some("We present memory-based language models.")
+        );
+        assert_eq!(
+            merged.keywords,
+            ["memory-based learning", "language models"]
+        );
+        assert_eq!(merged.persons.len(), 2);
+        assert_eq!(merged.duplicates, ["uuid-1"]);
+
+        let mut output = vec![];
+        write_tsv(&clusters, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.lines().count(), 3);
+        assert!(output.contains("uuid-4\tuuid-5\tdoi\t"));
+    }
+
+    #[test]
+    fn test_merge_external_persons() {
+        let mut canonical = ResearchClean::builder("uuid-1")
+            .persons(vec![
+                PersonRef::internal(0, "p1", "Peter Berck"),
+                PersonRef::external(1, "", "Ext One"),
+            ])
+            .build();
+        let duplicate = ResearchClean::builder("uuid-2")
+            .persons(vec![
+                PersonRef::internal(0, "p1", "Berck, Peter"),
+                PersonRef::external(1, "", "Ext Two"),
+                PersonRef::external(2, "", "ext one"),
+                PersonRef::external(3, "e3", "Ext Three"),
+            ])
+            .build();
+        merge(&mut canonical, duplicate);
+        let names: Vec<&str> = canonical.persons.iter().map(|p| p.get_name()).collect();
+        assert_eq!(names, ["Peter Berck", "Ext One", "Ext Two", "Ext Three"]);
+    }
+}
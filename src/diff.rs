@@ -0,0 +1,103 @@
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+// Compares two JSONL exports (one ResearchClean per line) on the "uuid"
+// field. Only useful if the uuids are stable between the runs, see the
+// uuidmap and salt options.
+
+#[derive(Debug, Default)]
+pub struct JsonlDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+    pub unchanged: usize,
+}
+
+impl JsonlDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+// uuid -> JSON object. BTreeMap so the output is sorted on uuid.
+fn read_jsonl_by_uuid(
+    file_path: &str,
+) -> Result<BTreeMap<String, Value>, Box<dyn std::error::Error>> {
+    let file = File::open(file_path)?;
+    let reader = BufReader::new(file);
+    let mut data = BTreeMap::new();
+
+    for (line_number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value: Value = serde_json::from_str(&line)?;
+        let uuid = value
+            .get("uuid")
+            .and_then(|uuid| uuid.as_str())
+            .ok_or_else(|| format!("No uuid in {}:{}", file_path, line_number + 1))?
+            .to_string();
+        data.insert(uuid, value);
+    }
+    Ok(data)
+}
+
+pub fn diff_jsonl(old_path: &str, new_path: &str) -> Result<JsonlDiff, Box<dyn std::error::Error>> {
+    let old = read_jsonl_by_uuid(old_path)?;
+    let new = read_jsonl_by_uuid(new_path)?;
+    let mut diff = JsonlDiff::default();
+
+    for (uuid, old_value) in &old {
+        match new.get(uuid) {
+            Some(new_value) if new_value == old_value => diff.unchanged += 1,
+            Some(_) => diff.changed.push(uuid.clone()),
+            None => diff.removed.push(uuid.clone()),
+        }
+    }
+    diff.added = new
+        .keys()
+        .filter(|uuid| !old.contains_key(*uuid))
+        .cloned()
+        .collect();
+
+    Ok(diff)
+}
+
+// ===========================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_jsonl() {
+        let dir = std::env::temp_dir();
+        let id = uuid::Uuid::new_v4();
+        let old_path = dir.join(format!("lucris_test_diff_old_{}.jsonl", id));
+        let new_path = dir.join(format!("lucris_test_diff_new_{}.jsonl", id));
+        std::fs::write(
+            &old_path,
+            "{\"uuid\":\"a\",\"title\":\"A\"}\n{\"uuid\":\"b\",\"title\":\"B\"}\n{\"uuid\":\"c\",\"title\":\"C\"}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &new_path,
+            "{\"uuid\":\"c\",\"title\":\"C\"}\n{\"uuid\":\"b\",\"title\":\"B2\"}\n{\"uuid\":\"d\",\"title\":\"D\"}\n",
+        )
+        .unwrap();
+
+        let diff = diff_jsonl(old_path.to_str().unwrap(), new_path.to_str().unwrap())
+            .expect("Failed to diff.");
+        assert_eq!(diff.removed, vec!["a"]);
+        assert_eq!(diff.added, vec!["d"]);
+        assert_eq!(diff.changed, vec!["b"]);
+        assert_eq!(diff.unchanged, 1);
+        assert!(!diff.is_empty());
+
+        let _ = std::fs::remove_file(old_path);
+        let _ = std::fs::remove_file(new_path);
+    }
+}
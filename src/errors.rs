@@ -1,7 +1,12 @@
+use clap::ValueEnum;
+use log::error;
+use serde::de::DeserializeOwned;
 use serde::Serialize;
+use std::fmt;
+use std::io::Write;
 
 // Errors thrown when doing a try_from_with_locale().
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub enum CleanError {
     MissingUUID,
     MissingNameField,
@@ -9,8 +14,327 @@ pub enum CleanError {
     MissingLastName,
 }
 
+impl fmt::Display for CleanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = match self {
+            CleanError::MissingUUID => "missing uuid",
+            CleanError::MissingNameField => "missing name field",
+            CleanError::MissingFirstName => "missing first name",
+            CleanError::MissingLastName => "missing last name",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+impl std::error::Error for CleanError {}
+
 // Errors thrown when working with Combined.
 #[derive(Debug, Serialize)]
 pub enum CombinedError {
     NoSuchUUID,
 }
+
+impl fmt::Display for CombinedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CombinedError::NoSuchUUID => write!(f, "no such uuid"),
+        }
+    }
+}
+
+impl std::error::Error for CombinedError {}
+
+/// Errors from parsing a filter expression, see filter_expr. The position
+/// is the character offset in the expression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExprError {
+    pub expression: String,
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ExprError {
+    // Prints the expression with a ^ under the problem.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} at position {}", self.message, self.position)?;
+        writeln!(f, "  {}", self.expression)?;
+        write!(f, "  {}^", " ".repeat(self.position))
+    }
+}
+
+impl std::error::Error for ExprError {}
+
+/// Errors from reading and converting the LUCRIS files. The uuids are the
+/// original LUCRIS uuids, so the broken records can be found in the dump.
+#[derive(Debug)]
+pub enum LucrisError {
+    /// The file could not be opened or read.
+    Io {
+        file: String,
+        source: std::io::Error,
+    },
+    /// A line could not be deserialised. `path` is the serde path to the
+    /// field which failed, e.g. `persons[2].name.firstName`.
+    Parse {
+        file: String,
+        line: usize,
+        uuid: Option<String>,
+        path: String,
+        message: String,
+    },
+    /// A record was parsed but could not be converted to a *Clean struct.
+    Clean {
+        file: String,
+        uuid: Option<String>,
+        error: CleanError,
+    },
+    /// A line of a list file (the opt-outs) could not be understood.
+    Invalid {
+        file: String,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for LucrisError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LucrisError::Io { file, source } => write!(f, "{}: {}", file, source),
+            LucrisError::Parse {
+                file,
+                line,
+                uuid,
+                path,
+                message,
+            } => write!(
+                f,
+                "{}:{}: uuid {} at {}: {}",
+                file,
+                line,
+                uuid.as_deref().unwrap_or("-"),
+                path,
+                message
+            ),
+            LucrisError::Clean { file, uuid, error } => write!(
+                f,
+                "{}: uuid {}: {}",
+                file,
+                uuid.as_deref().unwrap_or("-"),
+                error
+            ),
+            LucrisError::Invalid {
+                file,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", file, line, message),
+        }
+    }
+}
+
+impl std::error::Error for LucrisError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LucrisError::Io { source, .. } => Some(source),
+            LucrisError::Clean { error, .. } => Some(error),
+            LucrisError::Parse { .. } | LucrisError::Invalid { .. } => None,
+        }
+    }
+}
+
+impl LucrisError {
+    pub fn io(file: &str, source: std::io::Error) -> Self {
+        LucrisError::Io {
+            file: file.to_string(),
+            source,
+        }
+    }
+}
+
+/// Parses one JSONL line. On failure the error contains the serde path of
+/// the failing field, and the uuid if the line is valid JSON with a "uuid".
+pub fn parse_line<T: DeserializeOwned>(
+    file: &str,
+    line_number: usize,
+    line: &str,
+) -> Result<T, LucrisError> {
+    let deserializer = &mut serde_json::Deserializer::from_str(line);
+    serde_path_to_error::deserialize(deserializer).map_err(|e| {
+        let uuid = serde_json::from_str::<serde_json::Value>(line)
+            .ok()
+            .and_then(|v| v.get("uuid").and_then(|u| u.as_str()).map(String::from));
+        LucrisError::Parse {
+            file: file.to_string(),
+            line: line_number,
+            uuid,
+            path: e.path().to_string(),
+            message: e.inner().to_string(),
+        }
+    })
+}
+
+/// What to do with records which cannot be parsed or converted.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, ValueEnum)]
+pub enum ErrorPolicy {
+    /// Stop at the first bad record.
+    Strict,
+    /// Skip bad records, and remember them in the rejects.
+    #[default]
+    Lenient,
+}
+
+/// One line in the rejects file.
+#[derive(Debug, Clone, Serialize)]
+pub struct Reject {
+    pub file: String,
+    pub line: Option<usize>,
+    pub uuid: Option<String>,
+    pub stage: &'static str,
+    pub path: Option<String>,
+    pub reason: String,
+}
+
+impl Reject {
+    fn from_error(error: &LucrisError) -> Self {
+        match error {
+            LucrisError::Io { file, source } => Reject {
+                file: file.clone(),
+                line: None,
+                uuid: None,
+                stage: "io",
+                path: None,
+                reason: source.to_string(),
+            },
+            LucrisError::Parse {
+                file,
+                line,
+                uuid,
+                path,
+                message,
+            } => Reject {
+                file: file.clone(),
+                line: Some(*line),
+                uuid: uuid.clone(),
+                stage: "parse",
+                path: Some(path.clone()),
+                reason: message.clone(),
+            },
+            LucrisError::Clean { file, uuid, error } => Reject {
+                file: file.clone(),
+                line: None,
+                uuid: uuid.clone(),
+                stage: "clean",
+                path: None,
+                reason: error.to_string(),
+            },
+            LucrisError::Invalid {
+                file,
+                line,
+                message,
+            } => Reject {
+                file: file.clone(),
+                line: Some(*line),
+                uuid: None,
+                stage: "parse",
+                path: None,
+                reason: message.clone(),
+            },
+        }
+    }
+}
+
+/// Collects the skipped records. In strict mode the first one is returned
+/// as an error (and is also kept, so it ends up in the rejects file).
+#[derive(Debug, Default)]
+pub struct Rejects {
+    policy: ErrorPolicy,
+    rejects: Vec<Reject>,
+}
+
+impl Rejects {
+    pub fn new(policy: ErrorPolicy) -> Self {
+        Self {
+            policy,
+            rejects: vec![],
+        }
+    }
+
+    pub fn add(&mut self, error: LucrisError) -> Result<(), LucrisError> {
+        error!("{}", error);
+        self.rejects.push(Reject::from_error(&error));
+        match self.policy {
+            ErrorPolicy::Strict => Err(error),
+            ErrorPolicy::Lenient => Ok(()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.rejects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rejects.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Reject> {
+        self.rejects.iter()
+    }
+
+    /// One JSON object per rejected record.
+    pub fn write_jsonl<W: Write>(&self, mut output: W) -> Result<(), Box<dyn std::error::Error>> {
+        for reject in &self.rejects {
+            serde_json::to_writer(&mut output, reject)?;
+            writeln!(output)?;
+        }
+        Ok(())
+    }
+}
+
+// ===========================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json_research::ResearchJson;
+
+    #[test]
+    fn test_parse_line_path() {
+        let line = r#"{"uuid":"01234567-0123-0123-0123-0123456789AB","title":{"value":42}}"#;
+        let result = parse_line::<ResearchJson>("research.jsonl", 7, line);
+        match result {
+            Err(LucrisError::Parse {
+                line, uuid, path, ..
+            }) => {
+                assert_eq!(line, 7);
+                assert_eq!(
+                    uuid.as_deref(),
+                    Some("01234567-0123-0123-0123-0123456789AB")
+                );
+                assert_eq!(path, "title.value");
+            }
+            other => panic!("Expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_rejects_policy() {
+        let error = || LucrisError::Clean {
+            file: "persons.jsonl".to_string(),
+            uuid: None,
+            error: CleanError::MissingUUID,
+        };
+        let mut lenient = Rejects::new(ErrorPolicy::Lenient);
+        assert!(lenient.add(error()).is_ok());
+        assert!(lenient.add(error()).is_ok());
+        assert_eq!(lenient.len(), 2);
+
+        let mut strict = Rejects::new(ErrorPolicy::Strict);
+        assert!(strict.add(error()).is_err());
+        assert_eq!(strict.len(), 1);
+
+        let mut output = Vec::new();
+        strict.write_jsonl(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("\"stage\":\"clean\""));
+        assert!(output.contains("\"reason\":\"missing uuid\""));
+    }
+}
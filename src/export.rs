@@ -0,0 +1,376 @@
+use crate::combined::Combined;
+use crate::concept_index::WeightedConcept;
+use crate::formatting::extract_text_with_formatting;
+use crate::json_person::PersonClean;
+use crate::json_research::ResearchClean;
+use crate::org_tree::OrgPath;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::io::Write;
+
+// Output of the research in Combined. The functions take a writer, main()
+// passes a locked stdout. The order never depends on the HashMaps, so with
+// stable safe uuids two runs over the same input give the same output.
+
+/// The order of the research in the output. Ties are sorted on (safe)
+/// uuid.
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum OutputOrder {
+    /// Publication date, oldest first.
+    #[default]
+    Date,
+    /// Title, case insensitive.
+    Title,
+    /// The order in the research file.
+    Source,
+}
+
+/// The research in Combined in the given order.
+pub fn sorted_research(combined: &Combined, order: OutputOrder) -> Vec<&ResearchClean> {
+    let mut research: Vec<&ResearchClean> = combined.research.values().collect();
+    research.sort_by(|a, b| {
+        let ordering = match order {
+            OutputOrder::Date => a.get_publication_date().cmp(b.get_publication_date()),
+            OutputOrder::Title => compare_titles(a.get_title(), b.get_title()),
+            OutputOrder::Source => a.source_index.cmp(&b.source_index),
+        };
+        ordering.then_with(|| a.get_uuid().cmp(b.get_uuid()))
+    });
+    research
+}
+
+fn compare_titles(a: &str, b: &str) -> Ordering {
+    a.to_lowercase()
+        .cmp(&b.to_lowercase())
+        .then_with(|| a.cmp(b))
+}
+
+/// One JSON object (ResearchClean) per line.
+pub fn write_research_jsonl<W: Write>(
+    combined: &Combined,
+    order: OutputOrder,
+    mut output: W,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for item in sorted_research(combined, order) {
+        serde_json::to_writer(&mut output, item)?;
+        writeln!(output)?;
+    }
+    Ok(())
+}
+
+/// Tab separated output with a UTF-8 BOM (for Excel), used by the
+/// python scripts. Research without an abstract is skipped.
+pub fn write_research_tsv<W: Write>(
+    combined: &Combined,
+    order: OutputOrder,
+    mut output: W,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // UTF-8 BOM
+    output.write_all(b"\xEF\xBB\xBF")?;
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(b'\t')
+        .from_writer(output);
+    writer.write_record([
+        "names", "title", "keywords", "pubdate", "abstract", "concepts", "venue", "doi", "isbn",
+        "url",
+    ])?;
+
+    for r in sorted_research(combined, order) {
+        let names: Vec<_> = r
+            .persons
+            .iter()
+            //.filter(|p| p.is_internal()) // Filter by the `inex` variable
+            .map(|p| p.get_name())
+            .collect();
+        let keywords = r.get_keywords().join(",");
+        let abstract_text = r
+            .get_abstract()
+            .unwrap_or("")
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        if !abstract_text.is_empty() {
+            // skip empty abstract
+            let abstract_text = extract_text_with_formatting(&abstract_text);
+            writer.write_record([
+                names.join(","),
+                r.get_title().to_string(),
+                keywords.to_string(),
+                r.get_publication_date().to_string(),
+                abstract_text,
+                concept_labels(r.get_concepts()).join(","),
+                r.venue.to_string(),
+                r.identifiers.dois.join(","),
+                r.identifiers.isbns.join(","),
+                r.identifiers.url().unwrap_or_default(),
+            ])?;
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+// The concept labels, for the text outputs.
+fn concept_labels(concepts: &[WeightedConcept]) -> Vec<&str> {
+    concepts.iter().map(|c| c.label.as_str()).collect()
+}
+
+/// One line in the person -> research index.
+#[derive(Debug, Serialize)]
+struct IndexEntry<'a> {
+    person: &'a str,
+    name: Option<&'a str>,
+    research: &'a [String],
+}
+
+/// The person_research index from Combined, one person per line.
+pub fn write_index_jsonl<W: Write>(
+    combined: &Combined,
+    mut output: W,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut person_uuids: Vec<&String> = combined.person_research.keys().collect();
+    person_uuids.sort();
+
+    for person_uuid in person_uuids {
+        let entry = IndexEntry {
+            person: person_uuid,
+            name: combined.get_person_name(person_uuid),
+            research: &combined.person_research[person_uuid],
+        };
+        serde_json::to_writer(&mut output, &entry)?;
+        writeln!(output)?;
+    }
+    Ok(())
+}
+
+/// Short version of a research output in a PersonDocument.
+#[derive(Debug, Serialize)]
+pub struct ResearchSummary<'a> {
+    pub uuid: &'a str,
+    pub title: &'a str,
+    pub year: &'a str,
+    pub keywords: &'a [String],
+    pub concepts: &'a [WeightedConcept],
+}
+
+/// One document per person, with the titles, years and keywords of all
+/// their research (the research still in Combined, so after filtering).
+#[derive(Debug, Serialize)]
+pub struct PersonDocument<'a> {
+    pub uuid: &'a str,
+    pub name: &'a str,
+    pub orgunits: &'a [OrgPath],
+    pub titles: &'a [String],
+    pub keywords: &'a [String],
+    pub concepts: &'a [WeightedConcept],
+    pub profile_info: Option<String>,
+    pub research: Vec<ResearchSummary<'a>>,
+}
+
+impl<'a> PersonDocument<'a> {
+    pub fn new(person: &'a PersonClean, combined: &'a Combined) -> Self {
+        let mut research: Vec<&ResearchClean> = combined
+            .get_research_for_person_uuid(person.get_uuid())
+            .unwrap_or_default();
+        research.sort_by(|a, b| {
+            a.get_publication_date()
+                .cmp(b.get_publication_date())
+                .then_with(|| a.get_uuid().cmp(b.get_uuid()))
+        });
+
+        PersonDocument {
+            uuid: person.get_uuid(),
+            name: person.get_name(),
+            orgunits: person.get_orgunits(),
+            titles: person.get_titles(),
+            keywords: person.get_keywords(),
+            concepts: person.get_concepts(),
+            profile_info: person.get_profile_info().map(extract_text_with_formatting),
+            research: research
+                .into_iter()
+                .map(|r| ResearchSummary {
+                    uuid: r.get_uuid(),
+                    title: r.get_title(),
+                    // The publication date is YYYY, YYYY-MM or YYYY-MM-DD.
+                    year: r.get_publication_date().get(..4).unwrap_or(""),
+                    keywords: r.get_keywords(),
+                    concepts: r.get_concepts(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// All the persons in Combined as PersonDocuments, sorted on name.
+pub fn person_documents(combined: &Combined) -> Vec<PersonDocument<'_>> {
+    let mut documents: Vec<PersonDocument> = combined
+        .persons
+        .values()
+        .map(|person| PersonDocument::new(person, combined))
+        .collect();
+    documents.sort_by(|a, b| a.name.cmp(b.name).then_with(|| a.uuid.cmp(b.uuid)));
+    documents
+}
+
+/// One PersonDocument per line.
+pub fn write_persons_jsonl<W: Write>(
+    combined: &Combined,
+    mut output: W,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for document in person_documents(combined) {
+        serde_json::to_writer(&mut output, &document)?;
+        writeln!(output)?;
+    }
+    Ok(())
+}
+
+/// The PersonDocuments as plain text blocks, separated by an empty line.
+pub fn write_persons_text<W: Write>(
+    combined: &Combined,
+    mut output: W,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for document in person_documents(combined) {
+        writeln!(output, "NAME: {}", document.name)?;
+        let orgunit = document
+            .orgunits
+            .first()
+            .map(|orgunit| orgunit.get_name())
+            .filter(|name| !name.is_empty())
+            .unwrap_or("no organisation unit");
+        writeln!(output, "ORGUNIT: {}", orgunit)?;
+        for orgunit in document.orgunits {
+            writeln!(output, "ORGPATH: {}", orgunit.path.join(" > "))?;
+        }
+        if !document.titles.is_empty() {
+            writeln!(output, "TITLES: {}", document.titles.join(", "))?;
+        }
+        if !document.keywords.is_empty() {
+            writeln!(output, "KEYWORDS: {}", document.keywords.join(", "))?;
+        }
+        if !document.concepts.is_empty() {
+            writeln!(
+                output,
+                "CONCEPTS: {}",
+                concept_labels(document.concepts).join(", ")
+            )?;
+        }
+        if let Some(profile_info) = document.profile_info.as_deref().filter(|t| !t.is_empty()) {
+            let profile_info = profile_info
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ");
+            writeln!(output, "PROFILE: {}", profile_info)?;
+        }
+        writeln!(output, "RESEARCH:")?;
+        for r in &document.research {
+            if r.keywords.is_empty() {
+                writeln!(output, " - {} {}", r.year, r.title)?;
+            } else {
+                writeln!(
+                    output,
+                    " - {} {} ({})",
+                    r.year,
+                    r.title,
+                    r.keywords.join(", ")
+                )?;
+            }
+        }
+        writeln!(output)?;
+    }
+    Ok(())
+}
+
+// ===========================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::Rejects;
+    use crate::load::{load_persons, load_research, Lookups};
+    use crate::optout::OptOutPolicy;
+    use crate::uuid_map::UuidMap;
+    use std::path::Path;
+
+    fn make_test_path(file_name: &str) -> String {
+        let project_root = env!("CARGO_MANIFEST_DIR");
+        Path::new(project_root)
+            .join("tests")
+            .join("data")
+            .join(file_name)
+            .to_str()
+            .expect("Invalid path.")
+            .to_string()
+    }
+
+    #[test]
+    fn test_person_documents() {
+        let mut umap = UuidMap::new();
+        let mut rejects = Rejects::default();
+        let research = load_research(
+            &make_test_path("pjb_research.jsonl"),
+            &"en_GB".into(),
+            &mut umap,
+            &Lookups::default(),
+            &mut rejects,
+        )
+        .expect("Failed to load research.");
+        let persons = load_persons(
+            &make_test_path("pjb_persons.jsonl"),
+            &"en_GB".into(),
+            &mut umap,
+            &Lookups::default(),
+            &mut rejects,
+        )
+        .expect("Failed to load persons.");
+        let combined = Combined::new(research, persons, OptOutPolicy::default());
+
+        let documents = person_documents(&combined);
+        assert_eq!(documents.len(), 1);
+        assert_eq!(documents[0].name, "Peter Berck");
+        assert_eq!(
+            documents[0].orgunits[0].get_name(),
+            "Lund University Humanities Lab"
+        );
+        assert_eq!(documents[0].research.len(), 1);
+        assert_eq!(
+            documents[0].research[0].title,
+            "Memory-based Language Models"
+        );
+
+        let mut output = Vec::new();
+        write_persons_text(&combined, &mut output).expect("Failed to write text.");
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("NAME: Peter Berck\n"));
+        assert!(output.contains("Memory-based Language Models"));
+    }
+
+    #[test]
+    fn test_sorted_research() {
+        let mut research = std::collections::HashMap::new();
+        for (uuid, title, date, source_index) in [
+            ("c", "beta", "2020", 0),
+            ("b", "Alpha", "2019-05", 1),
+            ("a", "alpha", "2020", 2),
+        ] {
+            let mut item = ResearchClean::builder(uuid)
+                .title(title)
+                .publication_date(date)
+                .build();
+            item.source_index = source_index;
+            research.insert(uuid.to_string(), item);
+        }
+        let combined = Combined::new(research, Default::default(), OptOutPolicy::default());
+
+        let uuids = |order| -> Vec<&str> {
+            sorted_research(&combined, order)
+                .iter()
+                .map(|r| r.get_uuid())
+                .collect()
+        };
+        assert_eq!(uuids(OutputOrder::Date), vec!["b", "a", "c"]);
+        assert_eq!(uuids(OutputOrder::Title), vec!["b", "a", "c"]);
+        assert_eq!(uuids(OutputOrder::Source), vec!["c", "b", "a"]);
+    }
+}
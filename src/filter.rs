@@ -1,6 +1,10 @@
+use crate::filter_report::{Evidence, FilterReport};
+use crate::json_person::PersonClean;
 use crate::json_research::ResearchClean;
+use crate::names::{NameMatcher, NearMiss};
+use crate::org_tree::OrgTree;
 use clap::ValueEnum;
-use regex::{escape, RegexSet};
+use regex::{escape, Regex, RegexSet};
 use std::collections::{HashMap, HashSet};
 
 #[derive(Clone, Copy, ValueEnum)]
@@ -11,49 +15,89 @@ pub enum FilterMode {
     DeleteMatching,
 }
 
-impl ResearchClean {
-    pub fn has_person(&self, names: &HashSet<String>) -> bool {
-        self.persons
-            .iter()
-            .any(|person| names.contains(&person.get_name().trim().to_lowercase()))
-    }
+// The names of the persons in a research output, plus the nameVariants of
+// the ones we have in persons.
+fn person_names<'a>(
+    item: &'a ResearchClean,
+    persons: &'a HashMap<String, PersonClean>,
+) -> impl Iterator<Item = &'a str> {
+    item.persons.iter().flat_map(move |person| {
+        let variants = persons
+            .get(&person.uuid)
+            .map(|p| p.get_name_variants())
+            .unwrap_or_default();
+        std::iter::once(person.get_name()).chain(variants.iter().map(|v| v.as_str()))
+    })
 }
 
-// Confusing with two variables named keywords.
-impl ResearchClean {
-    pub fn has_keyword(&self, keywords: &HashSet<String>) -> bool {
-        self.keywords
-            .iter()
-            .any(|keyword| keywords.contains(&keyword.trim().to_lowercase()))
-    }
+// The first person name which matches, as Evidence. Also used for the
+// person terms in filter_expr.rs.
+pub(crate) fn person_evidence(
+    item: &ResearchClean,
+    matcher: &NameMatcher,
+    persons: &HashMap<String, PersonClean>,
+) -> Option<Evidence> {
+    person_names(item, persons).find_map(|name| {
+        let (listed, score) = matcher.best_match(name)?;
+        matcher.matches(name).then(|| Evidence {
+            field: "persons",
+            matched: name.to_string(),
+            pattern: Some(listed.to_string()),
+            start: None,
+            end: None,
+            score: Some(score),
+        })
+    })
 }
 
-pub fn filter_research_by_person(
+/// Filters on the persons with the NameMatcher, which handles diacritics,
+/// initials, nameVariants and a similarity threshold. Returns the names
+/// from the list which were close to a person but did not match.
+pub fn filter_research_by_person_fuzzy(
     research: &mut HashMap<String, ResearchClean>,
-    names: Vec<String>,
+    matcher: &NameMatcher,
+    persons: &HashMap<String, PersonClean>,
     mode: FilterMode,
-) {
-    // Convert so we can use contain().
-    // let names: HashSet<String> = names.into_iter().collect();
-    let names: HashSet<String> = names
-        .into_iter()
-        .map(|name| name.trim().to_lowercase())
-        .collect();
-
-    research.retain(|_, item| {
-        let matches = item.has_person(&names);
+    report: &mut FilterReport,
+) -> Vec<NearMiss> {
+    let near_misses = matcher.near_misses(
+        research
+            .values()
+            .flat_map(|item| person_names(item, persons)),
+    );
 
-        match mode {
-            FilterMode::KeepMatching => matches,
-            FilterMode::DeleteMatching => !matches,
-        }
+    research.retain(|uuid, item| {
+        let evidence = person_evidence(item, matcher, persons);
+        let keep = match mode {
+            FilterMode::KeepMatching => evidence.is_some(),
+            FilterMode::DeleteMatching => evidence.is_none(),
+        };
+        report.add(uuid, "names", keep, evidence);
+        keep
     });
+
+    near_misses
+}
+
+// Confusing with two variables named keywords.
+impl ResearchClean {
+    pub fn has_keyword(&self, keywords: &HashSet<String>) -> bool {
+        self.matching_keyword(keywords).is_some()
+    }
+
+    pub fn matching_keyword(&self, keywords: &HashSet<String>) -> Option<&str> {
+        self.keywords
+            .iter()
+            .find(|keyword| keywords.contains(&keyword.trim().to_lowercase()))
+            .map(|keyword| keyword.as_str())
+    }
 }
 
 pub fn filter_research_by_keyword(
     research: &mut HashMap<String, ResearchClean>,
     keywords: Vec<String>,
     mode: FilterMode,
+    report: &mut FilterReport,
 ) {
     let keywords: HashSet<String> = keywords
         .into_iter()
@@ -61,23 +105,57 @@ pub fn filter_research_by_keyword(
         .filter(|keyword| !keyword.is_empty())
         .collect();
 
-    research.retain(|_, item| {
-        let matches = item.has_keyword(&keywords);
-        match mode {
-            FilterMode::KeepMatching => matches,
-            FilterMode::DeleteMatching => !matches,
-        }
+    research.retain(|uuid, item| {
+        let evidence = item.matching_keyword(&keywords).map(|keyword| Evidence {
+            field: "keywords",
+            matched: keyword.to_string(),
+            pattern: None,
+            start: None,
+            end: None,
+            score: None,
+        });
+        let keep = match mode {
+            FilterMode::KeepMatching => evidence.is_some(),
+            FilterMode::DeleteMatching => evidence.is_none(),
+        };
+        report.add(uuid, "keywords", keep, evidence);
+        keep
     });
 }
 
 impl ResearchClean {
     pub fn abstract_matches_any(&self, patterns: &RegexSet) -> bool {
-        patterns.is_match(&self.abstract_text)
+        self.abstract_text
+            .as_deref()
+            .is_some_and(|text| patterns.is_match(text))
     }
 
     pub fn title_matches_any(&self, patterns: &RegexSet) -> bool {
         patterns.is_match(&self.title)
     }
+
+    // The first match in the abstract, or else in the title, as Evidence.
+    fn text_evidence(&self, regexes: &[Regex]) -> Option<Evidence> {
+        [
+            ("abstract", self.get_abstract()),
+            ("title", Some(self.get_title())),
+        ]
+        .into_iter()
+        .filter_map(|(field, text)| Some((field, text?)))
+        .find_map(|(field, text)| {
+            regexes.iter().find_map(|regex| {
+                let found = regex.find(text)?;
+                Some(Evidence {
+                    field,
+                    matched: found.as_str().to_string(),
+                    pattern: Some(regex.as_str().to_string()),
+                    start: Some(text[..found.start()].chars().count()),
+                    end: Some(text[..found.end()].chars().count()),
+                    score: None,
+                })
+            })
+        })
+    }
 }
 
 // Use a similar list to names. We use regexen so we can use \b boundary.
@@ -85,6 +163,7 @@ pub fn filter_research_by_abstract(
     research: &mut HashMap<String, ResearchClean>,
     terms: Vec<String>,
     mode: FilterMode,
+    report: &mut FilterReport,
 ) -> Result<(), regex::Error> {
     let patterns: Vec<String> = terms
         .into_iter()
@@ -100,20 +179,229 @@ pub fn filter_research_by_abstract(
         })
         .collect();
 
-    // Compile once.
+    // Compile once. The separate regexen are for the evidence in the report.
+    let regexes = patterns
+        .iter()
+        .map(|pattern| Regex::new(pattern))
+        .collect::<Result<Vec<Regex>, _>>()?;
     let patterns = RegexSet::new(patterns)?;
 
     // We also check in the title, so abstract + title!
-    research.retain(|_, item| {
+    research.retain(|uuid, item| {
         let matches = item.abstract_matches_any(&patterns) || item.title_matches_any(&patterns);
+        let keep = match mode {
+            FilterMode::KeepMatching => matches,
+            FilterMode::DeleteMatching => !matches,
+        };
+        let evidence = matches.then(|| item.text_evidence(&regexes)).flatten();
+        report.add(uuid, "abstract", keep, evidence);
+        keep
+    });
+
+    Ok(())
+}
+
+impl ResearchClean {
+    pub fn has_faculty(&self, faculties: &HashSet<String>) -> bool {
+        self.orgunits.iter().any(|orgunit| {
+            orgunit
+                .faculty
+                .as_ref()
+                .is_some_and(|faculty| faculties.contains(&faculty.trim().to_lowercase()))
+        })
+    }
+}
+
+// Only works when the research was loaded with an OrgTree, otherwise there
+// are no faculties and nothing matches.
+pub fn filter_research_by_faculty(
+    research: &mut HashMap<String, ResearchClean>,
+    faculties: Vec<String>,
+    mode: FilterMode,
+) {
+    let faculties: HashSet<String> = faculties
+        .into_iter()
+        .map(|faculty| faculty.trim().to_lowercase())
+        .filter(|faculty| !faculty.is_empty())
+        .collect();
 
+    research.retain(|_, item| {
+        let matches = item.has_faculty(&faculties);
         match mode {
             FilterMode::KeepMatching => matches,
             FilterMode::DeleteMatching => !matches,
         }
     });
+}
 
-    Ok(())
+impl ResearchClean {
+    pub fn has_orgunit(&self, uuids: &HashSet<String>) -> bool {
+        self.orgunits
+            .iter()
+            .any(|orgunit| uuids.contains(&orgunit.uuid))
+    }
+}
+
+// The units can be uuids or names, the units below them in the tree are
+// included. Matches on the managing unit and the organisational units.
+pub fn filter_research_by_orgunit(
+    research: &mut HashMap<String, ResearchClean>,
+    orgunits: Vec<String>,
+    orgtree: &OrgTree,
+    mode: FilterMode,
+) {
+    let uuids = orgtree.expand(&orgunits);
+
+    research.retain(|_, item| {
+        let matches = item.has_orgunit(&uuids);
+        match mode {
+            FilterMode::KeepMatching => matches,
+            FilterMode::DeleteMatching => !matches,
+        }
+    });
+}
+
+/// Parses a date bound: YYYY, YYYY-MM or YYYY-MM-DD, like the publication
+/// dates. The month and day have to exist ("2021-02-29" does not).
+pub fn parse_partial_date(date: &str) -> Result<String, String> {
+    let error = || format!("{date:?} is not YYYY, YYYY-MM or YYYY-MM-DD");
+    let parts: Vec<&str> = date.split('-').collect();
+    let well_formed = parts.len() <= 3
+        && parts[0].len() == 4
+        && parts[1..].iter().all(|part| part.len() == 2)
+        && parts
+            .iter()
+            .all(|part| part.chars().all(|c| c.is_ascii_digit()));
+    if !well_formed {
+        return Err(error());
+    }
+    let numbers: Vec<u32> = parts.iter().map(|part| part.parse().unwrap_or(0)).collect();
+    if let Some(&month) = numbers.get(1) {
+        if !(1..=12).contains(&month) {
+            return Err(error());
+        }
+    }
+    if let Some(&day) = numbers.get(2) {
+        let year = numbers[0];
+        let leap =
+            year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400));
+        let days = match numbers[1] {
+            2 if leap => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        };
+        if !(1..=days).contains(&day) {
+            return Err(error());
+        }
+    }
+    Ok(date.to_string())
+}
+
+// Compares partial dates (YYYY, YYYY-MM or YYYY-MM-DD) on the precision
+// they have in common, so "2020" falls in both "2020-06" ranges.
+fn date_prefix_cmp(date: &str, bound: &str) -> std::cmp::Ordering {
+    let len = date.len().min(bound.len());
+    date[..len].cmp(&bound[..len])
+}
+
+impl ResearchClean {
+    pub fn published_between(&self, since: Option<&str>, until: Option<&str>) -> bool {
+        let date = self.get_publication_date();
+        if date.is_empty() {
+            return false;
+        }
+        since.is_none_or(|since| date_prefix_cmp(date, since).is_ge())
+            && until.is_none_or(|until| date_prefix_cmp(date, until).is_le())
+    }
+
+    // The type matches on the full type, e.g. "contributiontojournal/article",
+    // or on one of its parts, e.g. "article" or "thesis".
+    pub fn has_research_type(&self, types: &HashSet<String>) -> bool {
+        let research_type = self.get_research_type().to_lowercase();
+        types.contains(&research_type) || research_type.split('/').any(|part| types.contains(part))
+    }
+
+    // The language matches on the full code ("en_gb") or the language
+    // part ("en").
+    pub fn has_language(&self, languages: &HashSet<String>) -> bool {
+        let language = self.get_language().to_lowercase();
+        languages.contains(&language)
+            || language
+                .split('_')
+                .next()
+                .is_some_and(|part| languages.contains(part))
+    }
+}
+
+// Research without a publication date never matches.
+pub fn filter_research_by_date(
+    research: &mut HashMap<String, ResearchClean>,
+    since: Option<&str>,
+    until: Option<&str>,
+    mode: FilterMode,
+) {
+    research.retain(|_, item| {
+        let matches = item.published_between(since, until);
+        match mode {
+            FilterMode::KeepMatching => matches,
+            FilterMode::DeleteMatching => !matches,
+        }
+    });
+}
+
+pub fn filter_research_by_type(
+    research: &mut HashMap<String, ResearchClean>,
+    types: Vec<String>,
+    mode: FilterMode,
+) {
+    let types: HashSet<String> = types
+        .into_iter()
+        .map(|research_type| research_type.trim().to_lowercase())
+        .filter(|research_type| !research_type.is_empty())
+        .collect();
+
+    research.retain(|_, item| {
+        let matches = item.has_research_type(&types);
+        match mode {
+            FilterMode::KeepMatching => matches,
+            FilterMode::DeleteMatching => !matches,
+        }
+    });
+}
+
+// Research without peer-review information counts as not peer reviewed.
+pub fn filter_research_by_peer_review(
+    research: &mut HashMap<String, ResearchClean>,
+    mode: FilterMode,
+) {
+    research.retain(|_, item| {
+        let matches = item.get_peer_review() == Some(true);
+        match mode {
+            FilterMode::KeepMatching => matches,
+            FilterMode::DeleteMatching => !matches,
+        }
+    });
+}
+
+pub fn filter_research_by_language(
+    research: &mut HashMap<String, ResearchClean>,
+    languages: Vec<String>,
+    mode: FilterMode,
+) {
+    let languages: HashSet<String> = languages
+        .into_iter()
+        .map(|language| language.trim().to_lowercase())
+        .filter(|language| !language.is_empty())
+        .collect();
+
+    research.retain(|_, item| {
+        let matches = item.has_language(&languages);
+        match mode {
+            FilterMode::KeepMatching => matches,
+            FilterMode::DeleteMatching => !matches,
+        }
+    });
 }
 
 // ===========================================================================
@@ -121,7 +409,11 @@ pub fn filter_research_by_abstract(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::json_research::read_research_jsonl;
+    use crate::errors::Rejects;
+    use crate::json_orgunits::read_orgunits_jsonl;
+    use crate::json_research::{read_research_jsonl, PersonRef};
+    use crate::names::INITIALS_SCORE;
+    use crate::org_tree::OrgPath;
     use crate::uuid_map::UuidMap;
     use std::path::{Path, PathBuf};
 
@@ -139,16 +431,17 @@ mod tests {
         let data_path = make_test_path("pjb_research.jsonl");
 
         // Read the original ResearchJson.
-        let read_umap = UuidMap::new();
-        let (research_json, _) =
-            read_research_jsonl(data_path.to_str().expect("Invalid path."), &read_umap)
-                .expect("Failed to read test data.");
+        let research_json = read_research_jsonl(
+            data_path.to_str().expect("Invalid path."),
+            &mut Rejects::default(),
+        )
+        .expect("Failed to read test data.");
 
         // Convert it to ResearchClean.
         let mut conversion_umap = UuidMap::new();
         let research_clean = ResearchClean::try_from_with_locale_umap(
             &research_json[0],
-            "en_GB",
+            &"en_GB".into(),
             &mut conversion_umap,
         )
         .expect("Failed to create ResearchClean");
@@ -164,6 +457,7 @@ mod tests {
             &mut rs_keep,
             vec!["language modeling".to_owned()],
             FilterMode::KeepMatching,
+            &mut FilterReport::default(),
         )
         .expect("Failed to compile regexp.");
 
@@ -178,6 +472,7 @@ mod tests {
             &mut rs_delete,
             vec!["approximations".to_owned()],
             FilterMode::DeleteMatching,
+            &mut FilterReport::default(),
         )
         .expect("Failed to compile regular expressions");
 
@@ -190,9 +485,280 @@ mod tests {
             &mut rs_sub,
             vec!["approx".to_owned()],
             FilterMode::KeepMatching,
+            &mut FilterReport::default(),
         )
         .expect("Failed to compile regular expressions");
 
         assert!(rs_sub.is_empty());
     }
+
+    #[test]
+    fn test_filter_research_by_faculty() {
+        let mut research = ResearchClean::builder("r1").title("Lab research").build();
+        research.orgunits = vec![OrgPath {
+            uuid: "lab".to_owned(),
+            path: vec!["LU".to_owned(), "Faculty".to_owned(), "Lab".to_owned()],
+            faculty: Some("Joint Faculties of Humanities and Theology".to_owned()),
+        }];
+        let other = ResearchClean::builder("r2").title("Other research").build();
+        let rs_clean = HashMap::from([("r1".to_owned(), research), ("r2".to_owned(), other)]);
+
+        let mut rs_keep = rs_clean.clone();
+        filter_research_by_faculty(
+            &mut rs_keep,
+            vec![" joint faculties of humanities and theology".to_owned()],
+            FilterMode::KeepMatching,
+        );
+        assert_eq!(rs_keep.len(), 1);
+        assert!(rs_keep.contains_key("r1"));
+
+        let mut rs_delete = rs_clean.clone();
+        filter_research_by_faculty(
+            &mut rs_delete,
+            vec!["Joint Faculties of Humanities and Theology".to_owned()],
+            FilterMode::DeleteMatching,
+        );
+        assert!(rs_delete.contains_key("r2"));
+        assert_eq!(rs_delete.len(), 1);
+    }
+
+    #[test]
+    fn test_filter_research_by_orgunit() {
+        let orgunits = read_orgunits_jsonl(
+            make_test_path("orgunits.jsonl")
+                .to_str()
+                .expect("Invalid path."),
+            &mut Rejects::default(),
+        )
+        .expect("Failed to read orgunits.");
+        let orgtree = OrgTree::new(&orgunits, "en_GB");
+
+        // Research from the division, below the centre.
+        let mut research = ResearchClean::builder("r1").title("Phonetics").build();
+        research.orgunits = vec![OrgPath::unresolved(
+            "a0000000-0000-0000-0000-000000000005",
+            None,
+        )];
+        let mut other = ResearchClean::builder("r2").title("Engineering").build();
+        other.orgunits = vec![OrgPath::unresolved(
+            "a0000000-0000-0000-0000-000000000006",
+            None,
+        )];
+        let rs_clean = HashMap::from([("r1".to_owned(), research), ("r2".to_owned(), other)]);
+
+        let mut rs_keep = rs_clean.clone();
+        filter_research_by_orgunit(
+            &mut rs_keep,
+            vec!["Centre for Languages and Literature".to_owned()],
+            &orgtree,
+            FilterMode::KeepMatching,
+        );
+        assert_eq!(rs_keep.len(), 1);
+        assert!(rs_keep.contains_key("r1"));
+
+        let mut rs_delete = rs_clean.clone();
+        filter_research_by_orgunit(
+            &mut rs_delete,
+            vec!["a0000000-0000-0000-0000-000000000004".to_owned()],
+            &orgtree,
+            FilterMode::DeleteMatching,
+        );
+        assert_eq!(rs_delete.len(), 1);
+        assert!(rs_delete.contains_key("r2"));
+    }
+
+    fn make_dated(uuid: &str, date: &str) -> ResearchClean {
+        ResearchClean::builder(uuid)
+            .publication_date(date)
+            .research_type("contributiontojournal/article")
+            .language("en_GB")
+            .build()
+    }
+
+    #[test]
+    fn test_filter_research_by_date() {
+        let rs_clean = HashMap::from([
+            ("r1".to_owned(), make_dated("r1", "2019-12-31")),
+            ("r2".to_owned(), make_dated("r2", "2020")),
+            ("r3".to_owned(), make_dated("r3", "2021-03")),
+            ("r4".to_owned(), make_dated("r4", "")),
+        ]);
+
+        let mut rs_since = rs_clean.clone();
+        filter_research_by_date(
+            &mut rs_since,
+            Some("2020-06"),
+            None,
+            FilterMode::KeepMatching,
+        );
+        let mut kept: Vec<&String> = rs_since.keys().collect();
+        kept.sort();
+        assert_eq!(kept, vec!["r2", "r3"]);
+
+        let mut rs_range = rs_clean.clone();
+        filter_research_by_date(
+            &mut rs_range,
+            Some("2019"),
+            Some("2020-12-31"),
+            FilterMode::DeleteMatching,
+        );
+        let mut kept: Vec<&String> = rs_range.keys().collect();
+        kept.sort();
+        assert_eq!(kept, vec!["r3", "r4"]);
+    }
+
+    #[test]
+    fn test_parse_partial_date() {
+        for date in ["2020", "2020-06", "2020-02-29", "2021-12-31"] {
+            assert_eq!(parse_partial_date(date).as_deref(), Ok(date));
+        }
+        for date in [
+            "",
+            "202",
+            "20201",
+            "2020-1",
+            "2020--",
+            "2020-13",
+            "2021-02-29",
+            "2020-04-31",
+            "2020-06-00",
+            "2020-06-01-",
+            "20x0",
+        ] {
+            assert!(parse_partial_date(date).is_err(), "{:?}", date);
+        }
+    }
+
+    #[test]
+    fn test_filter_research_by_type_and_language() {
+        let thesis = ResearchClean::builder("r2")
+            .research_type("thesis/doc")
+            .language("sv_SE")
+            .peer_review(Some(false))
+            .build();
+        let article = ResearchClean::builder("r1")
+            .research_type("contributiontojournal/article")
+            .language("en_GB")
+            .peer_review(Some(true))
+            .build();
+        let rs_clean = HashMap::from([("r1".to_owned(), article), ("r2".to_owned(), thesis)]);
+
+        let mut rs_type = rs_clean.clone();
+        filter_research_by_type(
+            &mut rs_type,
+            vec!["Article".to_owned()],
+            FilterMode::KeepMatching,
+        );
+        assert!(rs_type.contains_key("r1") && rs_type.len() == 1);
+
+        let mut rs_type = rs_clean.clone();
+        filter_research_by_type(
+            &mut rs_type,
+            vec!["thesis".to_owned()],
+            FilterMode::DeleteMatching,
+        );
+        assert!(rs_type.contains_key("r1") && rs_type.len() == 1);
+
+        let mut rs_lang = rs_clean.clone();
+        filter_research_by_language(
+            &mut rs_lang,
+            vec!["sv".to_owned()],
+            FilterMode::KeepMatching,
+        );
+        assert!(rs_lang.contains_key("r2") && rs_lang.len() == 1);
+
+        let mut rs_peer = rs_clean.clone();
+        filter_research_by_peer_review(&mut rs_peer, FilterMode::KeepMatching);
+        assert!(rs_peer.contains_key("r1") && rs_peer.len() == 1);
+    }
+
+    #[test]
+    fn test_filter_research_by_person() {
+        let research = ResearchClean::builder("r1")
+            .persons(vec![PersonRef::internal(0, "p1", "Åsa Björk")])
+            .build();
+        let rs_clean = HashMap::from([("r1".to_owned(), research)]);
+
+        // Misencoded and without diacritics.
+        let mut rs_keep = rs_clean.clone();
+        let matcher = NameMatcher::new(&["Bj√∂rk, √Ösa".to_owned()], 1.0, 0.8);
+        filter_research_by_person_fuzzy(
+            &mut rs_keep,
+            &matcher,
+            &HashMap::new(),
+            FilterMode::KeepMatching,
+            &mut FilterReport::default(),
+        );
+        assert_eq!(rs_keep.len(), 1);
+
+        // Initials are near misses at the default threshold.
+        let mut rs_keep = rs_clean.clone();
+        let matcher = NameMatcher::new(&["A. Bjork".to_owned(), "Asa Bjorn".to_owned()], 1.0, 0.8);
+        let near_misses = filter_research_by_person_fuzzy(
+            &mut rs_keep,
+            &matcher,
+            &HashMap::new(),
+            FilterMode::KeepMatching,
+            &mut FilterReport::default(),
+        );
+        assert_eq!(rs_keep.len(), 0);
+        assert_eq!(near_misses.len(), 2);
+        assert_eq!(near_misses[0].name, "A. Bjork");
+        assert_eq!(near_misses[1].name, "Asa Bjorn");
+        assert_eq!(near_misses[1].candidate, "Åsa Björk");
+
+        let mut rs_keep = rs_clean.clone();
+        let matcher = NameMatcher::new(&["A. Bjork".to_owned()], INITIALS_SCORE, 0.8);
+        let near_misses = filter_research_by_person_fuzzy(
+            &mut rs_keep,
+            &matcher,
+            &HashMap::new(),
+            FilterMode::KeepMatching,
+            &mut FilterReport::default(),
+        );
+        assert_eq!(rs_keep.len(), 1);
+        assert!(near_misses.is_empty());
+    }
+
+    #[test]
+    fn test_filter_report() {
+        let research = ResearchClean::builder("r1")
+            .title("Memory-based language models")
+            .abstract_text("Fast approximations of k-nearest neighbour classification.")
+            .keywords(vec!["NLP".to_owned()])
+            .build();
+        let other = ResearchClean::builder("r2").title("Other").build();
+        let mut rs_clean = HashMap::from([("r1".to_owned(), research), ("r2".to_owned(), other)]);
+
+        let mut report = FilterReport::new(true);
+        filter_research_by_keyword(
+            &mut rs_clean,
+            vec!["nlp".to_owned()],
+            FilterMode::KeepMatching,
+            &mut report,
+        );
+        filter_research_by_abstract(
+            &mut rs_clean,
+            vec!["Approximations".to_owned()],
+            FilterMode::KeepMatching,
+            &mut report,
+        )
+        .expect("Failed to compile regular expressions");
+        assert_eq!(rs_clean.len(), 1);
+
+        let entries: Vec<_> = report.iter().collect();
+        assert_eq!(entries.len(), 3);
+        let dropped = entries.iter().find(|e| e.uuid == "r2").unwrap();
+        assert!(!dropped.kept && dropped.evidence.is_none());
+        let evidence = entries[2].evidence.as_ref().unwrap();
+        assert_eq!(evidence.field, "abstract");
+        assert_eq!(evidence.matched, "approximations");
+        assert_eq!((evidence.start, evidence.end), (Some(5), Some(19)));
+
+        let mut output = vec![];
+        report.write_tsv(&mut output).unwrap();
+        let tsv = String::from_utf8(output).unwrap();
+        assert!(tsv.contains("r2\tkeywords\tdropped\t\t\t\t\t\t\n"));
+        assert!(tsv.contains("r1\tabstract\tkept\tabstract\tapproximations"));
+    }
 }
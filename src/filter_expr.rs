@@ -0,0 +1,506 @@
+use crate::errors::ExprError;
+use crate::filter::{parse_partial_date, person_evidence, FilterMode};
+use crate::json_person::PersonClean;
+use crate::json_research::ResearchClean;
+use crate::names::NameMatcher;
+use crate::org_tree::OrgTree;
+use regex::{escape, RegexSet};
+use std::collections::{HashMap, HashSet};
+
+// A small boolean language to combine the filters, for example:
+//
+//   person:"Peter Berck" AND (abstract:~"language model" OR keyword:NLP)
+//   AND NOT type:thesis
+//
+// Terms are field:value, values with spaces need double quotes. NOT binds
+// tighter than AND, which binds tighter than OR. The operators are case
+// insensitive. The terms use the same matching as the separate filters in
+// filter.rs, so keyword:nlp matches a whole keyword, abstract:word a whole
+// word in the abstract or title, and person:name uses the NameMatcher like
+// --names. With :~ the value is a regular expression instead.
+//
+// The fields are person, keyword, abstract, title, concept, type,
+// language, faculty, orgunit, since, until and peerreview (true/false).
+
+/// The parsed expression. The terms are compiled when parsing, so the
+/// regular expressions and orgunit lookups are done once.
+#[derive(Debug)]
+pub enum FilterExpr {
+    And(Box<FilterExpr>, Box<FilterExpr>),
+    Or(Box<FilterExpr>, Box<FilterExpr>),
+    Not(Box<FilterExpr>),
+    Term(Term),
+}
+
+#[derive(Debug)]
+pub enum Term {
+    Person(NameMatcher),
+    Keyword(HashSet<String>),
+    Concept(String),
+    // Matches the abstract or the title, like filter_research_by_abstract.
+    Abstract(RegexSet),
+    Title(RegexSet),
+    Type(HashSet<String>),
+    Language(HashSet<String>),
+    Faculty(HashSet<String>),
+    Orgunit(HashSet<String>),
+    Since(String),
+    Until(String),
+    PeerReview(bool),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    // field, regex (:~), value.
+    Term(String, bool, String),
+}
+
+// Splits the expression in (position, token) pairs.
+fn tokenize(expression: &str) -> Result<Vec<(usize, Token)>, ExprError> {
+    let error = |position: usize, message: &str| ExprError {
+        expression: expression.to_string(),
+        position,
+        message: message.to_string(),
+    };
+    let chars: Vec<char> = expression.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c == '(' || c == ')' {
+            tokens.push((
+                i,
+                if c == '(' {
+                    Token::LParen
+                } else {
+                    Token::RParen
+                },
+            ));
+            i += 1;
+            continue;
+        }
+
+        // A word, up to a colon, space or parenthesis.
+        let start = i;
+        while i < chars.len() && !chars[i].is_whitespace() && !"():\"".contains(chars[i]) {
+            i += 1;
+        }
+        let word: String = chars[start..i].iter().collect();
+        if i >= chars.len() || chars[i] != ':' {
+            let token = match word.to_uppercase().as_str() {
+                "AND" => Token::And,
+                "OR" => Token::Or,
+                "NOT" => Token::Not,
+                "" => return Err(error(start, "unexpected '\"'")),
+                _ => return Err(error(start, "expected field:value, AND, OR or NOT")),
+            };
+            tokens.push((start, token));
+            continue;
+        }
+        if word.is_empty() {
+            return Err(error(start, "missing field name"));
+        }
+
+        // Skip the colon, and the tilde for a regular expression.
+        i += 1;
+        let regex = i < chars.len() && chars[i] == '~';
+        if regex {
+            i += 1;
+        }
+
+        let value_start = i;
+        let mut value = String::new();
+        if i < chars.len() && chars[i] == '"' {
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(error(value_start, "unterminated string")),
+                    Some('"') => break,
+                    Some('\\') if i + 1 < chars.len() => {
+                        value.push(chars[i + 1]);
+                        i += 2;
+                    }
+                    Some(c) => {
+                        value.push(*c);
+                        i += 1;
+                    }
+                }
+            }
+            i += 1;
+        } else {
+            while i < chars.len() && !chars[i].is_whitespace() && !"()\"".contains(chars[i]) {
+                value.push(chars[i]);
+                i += 1;
+            }
+        }
+        if value.trim().is_empty() {
+            return Err(error(value_start, "missing value"));
+        }
+        tokens.push((start, Token::Term(word.to_lowercase(), regex, value)));
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    expression: &'a str,
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    orgtree: &'a OrgTree,
+    name_threshold: f64,
+}
+
+impl Parser<'_> {
+    fn error(&self, position: usize, message: impl Into<String>) -> ExprError {
+        ExprError {
+            expression: self.expression.to_string(),
+            position,
+            message: message.into(),
+        }
+    }
+
+    // The position of the current token, or the end of the expression.
+    fn position(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .map(|(position, _)| *position)
+            .unwrap_or(self.expression.chars().count())
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, token)| token)
+    }
+
+    fn parse_or(&mut self) -> Result<FilterExpr, ExprError> {
+        let mut left = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            let right = self.parse_and()?;
+            left = FilterExpr::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<FilterExpr, ExprError> {
+        let mut left = self.parse_not()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            let right = self.parse_not()?;
+            left = FilterExpr::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<FilterExpr, ExprError> {
+        if self.peek() == Some(&Token::Not) {
+            self.pos += 1;
+            return Ok(FilterExpr::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_atom()
+    }
+
+    fn parse_atom(&mut self) -> Result<FilterExpr, ExprError> {
+        let position = self.position();
+        match self.tokens.get(self.pos).cloned() {
+            Some((_, Token::LParen)) => {
+                self.pos += 1;
+                let expr = self.parse_or()?;
+                if self.peek() != Some(&Token::RParen) {
+                    return Err(self.error(self.position(), "expected ')'"));
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            Some((_, Token::Term(field, regex, value))) => {
+                self.pos += 1;
+                let term = self.make_term(position, &field, regex, &value)?;
+                Ok(FilterExpr::Term(term))
+            }
+            Some(_) => Err(self.error(position, "expected field:value or '('")),
+            None => Err(self.error(position, "unexpected end of expression")),
+        }
+    }
+
+    fn make_term(
+        &self,
+        position: usize,
+        field: &str,
+        regex: bool,
+        value: &str,
+    ) -> Result<Term, ExprError> {
+        let value = value.trim();
+        let lowercase = || HashSet::from([value.to_lowercase()]);
+        if regex && !matches!(field, "abstract" | "title") {
+            return Err(self.error(position, format!("{field} does not take :~")));
+        }
+        let term = match field {
+            // No near misses are reported for expressions.
+            "person" => Term::Person(NameMatcher::new(
+                &[value.to_string()],
+                self.name_threshold,
+                self.name_threshold,
+            )),
+            "keyword" => Term::Keyword(lowercase()),
+            "concept" => Term::Concept(value.to_lowercase()),
+            "abstract" | "title" => {
+                let pattern = if regex {
+                    format!("(?i){}", value)
+                } else {
+                    format!(r"(?i)\b{}\b", escape(value))
+                };
+                let patterns = RegexSet::new([pattern])
+                    .map_err(|e| self.error(position, format!("invalid regex: {e}")))?;
+                if field == "abstract" {
+                    Term::Abstract(patterns)
+                } else {
+                    Term::Title(patterns)
+                }
+            }
+            "type" => Term::Type(lowercase()),
+            "language" => Term::Language(lowercase()),
+            "faculty" => Term::Faculty(lowercase()),
+            "orgunit" => Term::Orgunit(self.orgtree.expand(&[value.to_string()])),
+            "since" | "until" => {
+                let date = parse_partial_date(value)
+                    .map_err(|_| self.error(position, "expected YYYY, YYYY-MM or YYYY-MM-DD"))?;
+                if field == "since" {
+                    Term::Since(date)
+                } else {
+                    Term::Until(date)
+                }
+            }
+            "peerreview" => match value.to_lowercase().as_str() {
+                "true" | "yes" => Term::PeerReview(true),
+                "false" | "no" => Term::PeerReview(false),
+                _ => return Err(self.error(position, "expected peerreview:true or false")),
+            },
+            _ => return Err(self.error(position, format!("unknown field {field:?}"))),
+        };
+        Ok(term)
+    }
+}
+
+impl FilterExpr {
+    /// Parses an expression. The OrgTree is used for the orgunit terms, and
+    /// can be empty. The name_threshold is the minimum similarity for the
+    /// person terms, as in NameMatcher.
+    pub fn parse(
+        expression: &str,
+        orgtree: &OrgTree,
+        name_threshold: f64,
+    ) -> Result<Self, ExprError> {
+        let mut parser = Parser {
+            expression,
+            tokens: tokenize(expression)?,
+            pos: 0,
+            orgtree,
+            name_threshold,
+        };
+        let expr = parser.parse_or()?;
+        if parser.pos < parser.tokens.len() {
+            return Err(parser.error(parser.position(), "expected AND, OR or end of expression"));
+        }
+        Ok(expr)
+    }
+
+    /// The persons are used for the nameVariants in the person terms.
+    pub fn matches(&self, item: &ResearchClean, persons: &HashMap<String, PersonClean>) -> bool {
+        match self {
+            FilterExpr::And(left, right) => {
+                left.matches(item, persons) && right.matches(item, persons)
+            }
+            FilterExpr::Or(left, right) => {
+                left.matches(item, persons) || right.matches(item, persons)
+            }
+            FilterExpr::Not(expr) => !expr.matches(item, persons),
+            FilterExpr::Term(term) => term.matches(item, persons),
+        }
+    }
+}
+
+impl Term {
+    pub fn matches(&self, item: &ResearchClean, persons: &HashMap<String, PersonClean>) -> bool {
+        match self {
+            Term::Person(matcher) => person_evidence(item, matcher, persons).is_some(),
+            Term::Keyword(keywords) => item.has_keyword(keywords),
+            Term::Concept(label) => item
+                .get_concepts()
+                .iter()
+                .any(|c| c.label.to_lowercase() == *label),
+            Term::Abstract(patterns) => {
+                item.abstract_matches_any(patterns) || item.title_matches_any(patterns)
+            }
+            Term::Title(patterns) => item.title_matches_any(patterns),
+            Term::Type(types) => item.has_research_type(types),
+            Term::Language(languages) => item.has_language(languages),
+            Term::Faculty(faculties) => item.has_faculty(faculties),
+            Term::Orgunit(uuids) => item.has_orgunit(uuids),
+            Term::Since(since) => item.published_between(Some(since), None),
+            Term::Until(until) => item.published_between(None, Some(until)),
+            Term::PeerReview(peer_review) => {
+                item.get_peer_review().unwrap_or(false) == *peer_review
+            }
+        }
+    }
+}
+
+pub fn filter_research_by_expr(
+    research: &mut HashMap<String, ResearchClean>,
+    expr: &FilterExpr,
+    persons: &HashMap<String, PersonClean>,
+    mode: FilterMode,
+) {
+    research.retain(|_, item| {
+        let matches = expr.matches(item, persons);
+        match mode {
+            FilterMode::KeepMatching => matches,
+            FilterMode::DeleteMatching => !matches,
+        }
+    });
+}
+
+// ===========================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json_research::PersonRef;
+    use crate::names::INITIALS_SCORE;
+
+    fn parse(expression: &str) -> Result<FilterExpr, ExprError> {
+        FilterExpr::parse(expression, &OrgTree::default(), 1.0)
+    }
+
+    fn make_research() -> HashMap<String, ResearchClean> {
+        let article = ResearchClean::builder("r1")
+            .title("Memory-based language models")
+            .abstract_text("We present memory-based language modeling.")
+            .keywords(vec!["NLP".to_owned(), "AI".to_owned()])
+            .persons(vec![PersonRef::internal(0, "p1", "Peter Berck")])
+            .publication_date("2025-10-25")
+            .research_type("contributiontojournal/article")
+            .build();
+        let thesis = ResearchClean::builder("r2")
+            .title("A thesis on language models")
+            .keywords(vec!["NLP".to_owned()])
+            .persons(vec![PersonRef::internal(0, "p1", "Peter Berck")])
+            .publication_date("2019")
+            .research_type("thesis/doc")
+            .build();
+        let other = ResearchClean::builder("r3")
+            .title("Something else")
+            .keywords(vec!["Y".to_owned()])
+            .persons(vec![PersonRef::internal(0, "p2", "Someone Else")])
+            .publication_date("2024")
+            .research_type("contributiontojournal/article")
+            .build();
+        HashMap::from([
+            ("r1".to_owned(), article),
+            ("r2".to_owned(), thesis),
+            ("r3".to_owned(), other),
+        ])
+    }
+
+    fn kept(expression: &str) -> Vec<String> {
+        let mut research = make_research();
+        let expr = parse(expression).expect("Failed to parse expression.");
+        filter_research_by_expr(
+            &mut research,
+            &expr,
+            &HashMap::new(),
+            FilterMode::KeepMatching,
+        );
+        let mut kept: Vec<String> = research.into_keys().collect();
+        kept.sort();
+        kept
+    }
+
+    #[test]
+    fn test_filter_expr() {
+        assert_eq!(
+            kept(
+                r#"person:"Peter Berck" AND (abstract:~"language model" OR keyword:NLP) AND NOT type:thesis"#
+            ),
+            vec!["r1"]
+        );
+        assert_eq!(kept("keyword:nlp or keyword:y"), vec!["r1", "r2", "r3"]);
+        // AND binds tighter than OR.
+        assert_eq!(
+            kept("keyword:y OR keyword:nlp AND since:2020"),
+            vec!["r1", "r3"]
+        );
+        assert_eq!(kept("NOT NOT type:article AND until:2024-12"), vec!["r3"]);
+        assert_eq!(kept("title:models"), vec!["r1", "r2"]);
+        assert_eq!(kept("title:model"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_filter_expr_person() {
+        // The person terms match like --names, with the threshold.
+        let mut research = make_research();
+        let expr = FilterExpr::parse(r#"person:"Berck, P.""#, &OrgTree::default(), 1.0)
+            .expect("Failed to parse expression.");
+        filter_research_by_expr(
+            &mut research,
+            &expr,
+            &HashMap::new(),
+            FilterMode::KeepMatching,
+        );
+        assert!(research.is_empty());
+
+        let mut research = make_research();
+        let expr = FilterExpr::parse(r#"person:"Berck, P.""#, &OrgTree::default(), INITIALS_SCORE)
+            .expect("Failed to parse expression.");
+        filter_research_by_expr(
+            &mut research,
+            &expr,
+            &HashMap::new(),
+            FilterMode::KeepMatching,
+        );
+        let mut kept: Vec<String> = research.into_keys().collect();
+        kept.sort();
+        assert_eq!(kept, vec!["r1", "r2"]);
+    }
+
+    #[test]
+    fn test_filter_expr_errors() {
+        let error = parse("keyword:nlp AND (person:x").unwrap_err();
+        assert_eq!(error.message, "expected ')'");
+        assert_eq!(error.position, 25);
+
+        let error = parse("keyword:nlp person:x").unwrap_err();
+        assert_eq!(error.position, 12);
+
+        let error = parse("colour:red").unwrap_err();
+        assert_eq!(error.message, "unknown field \"colour\"");
+
+        assert_eq!(
+            parse(r#"title:"open"#).unwrap_err().message,
+            "unterminated string"
+        );
+        assert_eq!(parse("keyword:").unwrap_err().message, "missing value");
+        assert_eq!(parse("nlp").unwrap_err().position, 0);
+        assert!(parse("abstract:~\"(unclosed\"").is_err());
+        assert!(parse("keyword:~nlp").is_err());
+        assert!(parse("keyword:nlp AND").is_err());
+        for date in [
+            "since:20201",
+            "since:2020-1",
+            "until:2020--",
+            "until:2020-13",
+        ] {
+            assert_eq!(
+                parse(date).unwrap_err().message,
+                "expected YYYY, YYYY-MM or YYYY-MM-DD"
+            );
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use serde::Serialize;
+use std::io::Write;
+
+// The filter report: for every research output a filter looked at, whether
+// it was kept or dropped, and what matched. The research uuids are the safe
+// uuids. A dropped research output is not seen by the filters after it, so
+// its last entry is the filter which decided on it.
+
+/// What matched in a research output.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Evidence {
+    // "persons", "keywords", "title" or "abstract".
+    pub field: &'static str,
+    // The matched text: a person name, a keyword or the regex match.
+    pub matched: String,
+    // The name from the names list, or the regular expression.
+    pub pattern: Option<String>,
+    // Character offsets of the match in the title or abstract.
+    pub start: Option<usize>,
+    pub end: Option<usize>,
+    // The name similarity.
+    pub score: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ReportEntry {
+    pub uuid: String,
+    pub filter: &'static str,
+    pub kept: bool,
+    pub evidence: Option<Evidence>,
+}
+
+/// Collects the ReportEntries. A disabled report (the default) ignores
+/// them, so the filters can always take one.
+#[derive(Debug, Default)]
+pub struct FilterReport {
+    enabled: bool,
+    entries: Vec<ReportEntry>,
+}
+
+impl FilterReport {
+    pub fn new(enabled: bool) -> Self {
+        FilterReport {
+            enabled,
+            entries: vec![],
+        }
+    }
+
+    pub fn add(
+        &mut self,
+        uuid: &str,
+        filter: &'static str,
+        kept: bool,
+        evidence: Option<Evidence>,
+    ) {
+        if self.enabled {
+            self.entries.push(ReportEntry {
+                uuid: uuid.to_string(),
+                filter,
+                kept,
+                evidence,
+            });
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ReportEntry> {
+        self.entries.iter()
+    }
+
+    // The entries per filter in the order they ran, and sorted on uuid
+    // within a filter, so the report is the same between runs.
+    fn sorted(&self) -> Vec<&ReportEntry> {
+        let mut entries: Vec<(usize, &ReportEntry)> = self.entries.iter().enumerate().collect();
+        let mut order: Vec<&'static str> = vec![];
+        for entry in &self.entries {
+            if !order.contains(&entry.filter) {
+                order.push(entry.filter);
+            }
+        }
+        let rank = |filter: &str| order.iter().position(|f| *f == filter);
+        entries.sort_by(|(a_idx, a), (b_idx, b)| {
+            rank(a.filter)
+                .cmp(&rank(b.filter))
+                .then_with(|| a.uuid.cmp(&b.uuid))
+                .then_with(|| a_idx.cmp(b_idx))
+        });
+        entries.into_iter().map(|(_, entry)| entry).collect()
+    }
+
+    /// One ReportEntry per line.
+    pub fn write_jsonl<W: Write>(&self, mut output: W) -> Result<(), Box<dyn std::error::Error>> {
+        for entry in self.sorted() {
+            serde_json::to_writer(&mut output, entry)?;
+            writeln!(output)?;
+        }
+        Ok(())
+    }
+
+    /// Tab separated, empty fields when there is no evidence.
+    pub fn write_tsv<W: Write>(&self, output: W) -> Result<(), Box<dyn std::error::Error>> {
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(b'\t')
+            .from_writer(output);
+        writer.write_record([
+            "uuid", "filter", "decision", "field", "matched", "pattern", "start", "end", "score",
+        ])?;
+        let optional = |value: Option<String>| value.unwrap_or_default();
+        for entry in self.sorted() {
+            let evidence = entry.evidence.as_ref();
+            writer.write_record([
+                entry.uuid.clone(),
+                entry.filter.to_string(),
+                if entry.kept { "kept" } else { "dropped" }.to_string(),
+                optional(evidence.map(|e| e.field.to_string())),
+                optional(evidence.map(|e| e.matched.clone())),
+                optional(evidence.and_then(|e| e.pattern.clone())),
+                optional(evidence.and_then(|e| e.start).map(|s| s.to_string())),
+                optional(evidence.and_then(|e| e.end).map(|e| e.to_string())),
+                optional(evidence.and_then(|e| e.score).map(|s| format!("{:.3}", s))),
+            ])?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
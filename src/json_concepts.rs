@@ -1,12 +1,7 @@
 #![allow(non_snake_case)]
-use log::{debug, error, info, warn};
-use rayon::iter::ParallelBridge;
-use rayon::iter::ParallelIterator;
+use crate::errors::{LucrisError, Rejects};
+use crate::jsonl::{read_all, JsonlReader, JsonlRecord};
 use serde::{Deserialize, Serialize};
-use std::fs::File;
-use std::io::BufRead;
-use std::io::BufReader;
-use std::sync::{Arc, Mutex};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ConceptJson {
@@ -92,44 +87,15 @@ impl ConceptJson {
     }
 }
 
-pub fn read_concept_jsonl(file_path: &str) -> Result<Vec<ConceptJson>, Box<dyn std::error::Error>> {
-    let file = File::open(file_path)?;
-    let reader = BufReader::new(file);
-    let data = Arc::new(Mutex::new(vec![]));
-    let failed_count = Arc::new(Mutex::new(0));
-
-    reader
-        .lines()
-        .map_while(Result::ok)
-        .par_bridge() // parallelise
-        .for_each(|line: String| {
-            match serde_json::from_str::<ConceptJson>(&line) {
-                Ok(json) => {
-                    debug!("uuid={:?}", json.uuid);
-
-                    // Add it to the data vector.
-                    let mut data = data.lock().unwrap();
-                    data.push(json);
-                }
-                Err(e) => {
-                    error!("{}", e);
-                    //panic!("{}", line);
-
-                    // Increment the failure counter.
-                    let mut failed = failed_count.lock().unwrap();
-                    *failed += 1;
-                }
-            }
-        });
-
-    if *failed_count.lock().unwrap() > 0 {
-        warn!("Failed to parse {} lines.", *failed_count.lock().unwrap());
-    }
+impl JsonlRecord for ConceptJson {
+    const KIND: &'static str = "concepts";
+}
 
-    // Extract the data from Arc<Mutex<...>> and return it.
-    let extracted_data = Arc::try_unwrap(data).unwrap().into_inner().unwrap();
-    info!("Extracted {} entries.", extracted_data.len());
-    Ok(extracted_data)
+pub fn read_concept_jsonl(
+    file_path: &str,
+    rejects: &mut Rejects,
+) -> Result<Vec<ConceptJson>, LucrisError> {
+    read_all(JsonlReader::<ConceptJson>::open(file_path)?, rejects)
 }
 
 #[cfg(test)]
@@ -1,12 +1,7 @@
 #![allow(non_snake_case)]
-use log::{debug, error, info, warn};
-use rayon::iter::ParallelBridge;
-use rayon::iter::ParallelIterator;
+use crate::errors::{LucrisError, Rejects};
+use crate::jsonl::{read_all, JsonlReader, JsonlRecord};
 use serde::{Deserialize, Serialize};
-use std::fs::File;
-use std::io::BufRead;
-use std::io::BufReader;
-use std::sync::{Arc, Mutex};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FingerprintJson {
@@ -61,7 +56,7 @@ impl FingerprintJson {
             .iter()
             .filter_map(|c| {
                 let uuid = c.uuid.as_ref()?;
-                let rank = c.weightedRank.unwrap_or(0.0) as f64;
+                let rank = c.weightedRank.unwrap_or(0.0);
                 Some((uuid.clone(), rank))
             })
             .collect::<Vec<_>>();
@@ -83,44 +78,13 @@ impl FingerprintJson {
     }
 }
 
+impl JsonlRecord for FingerprintJson {
+    const KIND: &'static str = "fingerprints";
+}
+
 pub fn read_fingerprint_jsonl(
     file_path: &str,
-) -> Result<Vec<FingerprintJson>, Box<dyn std::error::Error>> {
-    let file = File::open(file_path)?;
-    let reader = BufReader::new(file);
-    let data = Arc::new(Mutex::new(vec![]));
-    let failed_count = Arc::new(Mutex::new(0));
-
-    reader
-        .lines()
-        .filter_map(|line: Result<String, _>| line.ok())
-        .par_bridge() // parallelise
-        .for_each(|line: String| {
-            match serde_json::from_str::<FingerprintJson>(&line) {
-                Ok(json) => {
-                    debug!("uuid={:?}", json.uuid);
-
-                    // Add it to the data vector.
-                    let mut data = data.lock().unwrap();
-                    data.push(json);
-                }
-                Err(e) => {
-                    error!("{}", e);
-                    //panic!("{}", line);
-
-                    // Increment the failure counter.
-                    let mut failed = failed_count.lock().unwrap();
-                    *failed += 1;
-                }
-            }
-        });
-
-    if *failed_count.lock().unwrap() > 0 {
-        warn!("Failed to parse {} lines.", *failed_count.lock().unwrap());
-    }
-
-    // Extract the data from Arc<Mutex<...>> and return it.
-    let extracted_data = Arc::try_unwrap(data).unwrap().into_inner().unwrap();
-    info!("Extracted {} entries.", extracted_data.len());
-    Ok(extracted_data)
+    rejects: &mut Rejects,
+) -> Result<Vec<FingerprintJson>, LucrisError> {
+    read_all(JsonlReader::<FingerprintJson>::open(file_path)?, rejects)
 }
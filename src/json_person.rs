@@ -1,16 +1,16 @@
 #![allow(non_snake_case)]
-use crate::errors::CleanError;
+use crate::concept_index::WeightedConcept;
+use crate::errors::{CleanError, LucrisError, Rejects};
+use crate::jsonl::{read_all, JsonlReader, JsonlRecord};
+use crate::language::LanguageTags;
+use crate::locale::LocaleChain;
+use crate::org_tree::OrgPath;
 use crate::uuid_map::UuidMap;
-use log::{debug, error, info, trace, warn};
-use rayon::iter::ParallelBridge;
-use rayon::iter::ParallelIterator;
+use log::trace;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::convert::TryFrom;
 use std::fmt;
-use std::fs::File;
-use std::io::BufRead;
-use std::io::BufReader;
-use std::sync::{Arc, Mutex};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PersonJson {
@@ -49,9 +49,35 @@ pub struct PersonJson {
 pub struct PersonClean {
     uuid: String,
     name: String,
-    profile_info: String,
+    // Other spellings of the name, from nameVariants.
+    name_variants: Vec<String>,
+    // None when there is no profile text, or it is boilerplate, see
+    // Boilerplate.
+    profile_info: Option<String>,
     titles: Vec<String>,
     keywords: Vec<String>,
+    concepts: Vec<WeightedConcept>,
+    // The current orgunits, the first one is the main one, see get_orgunit().
+    orgunits: Vec<OrgPath>,
+    // The locale each text field was taken from, see LocaleChain.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    locales: BTreeMap<String, String>,
+    // The texts per locale, only in bilingual mode.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    translations: BTreeMap<String, PersonTexts>,
+    // The detected language of the profile text, see LanguageTags.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    detected_languages: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    language_mismatches: Vec<String>,
+}
+
+/// The texts of a person in one locale.
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct PersonTexts {
+    pub profile_info: Option<String>,
+    pub titles: Vec<String>,
+    pub keywords: Vec<String>,
 }
 
 // We may need a mapping from (safe-)uuid to PersonClean?
@@ -94,9 +120,18 @@ impl TryFrom<&PersonJson> for PersonClean {
         Ok(PersonClean {
             uuid: uuid.to_string(),
             name: full_name,
-            profile_info: "".to_string(), // Take a default en_GB locale?
+            name_variants: value.get_name_variants(),
+            profile_info: None, // Take a default en_GB locale?
             titles,
             keywords,
+            concepts: vec![],
+            // Which associations count depends on the reference date, the
+            // orgunits are set in load_persons().
+            orgunits: vec![],
+            locales: BTreeMap::new(),
+            translations: BTreeMap::new(),
+            detected_languages: BTreeMap::new(),
+            language_mismatches: vec![],
         })
     }
 }
@@ -106,17 +141,87 @@ impl PersonClean {
     pub fn get_name(&self) -> &str {
         &self.name
     }
+
+    pub fn get_uuid(&self) -> &str {
+        &self.uuid
+    }
+
+    pub fn get_name_variants(&self) -> &[String] {
+        &self.name_variants
+    }
+
+    pub fn get_profile_info(&self) -> Option<&str> {
+        self.profile_info.as_deref()
+    }
+
+    pub fn get_locales(&self) -> &BTreeMap<String, String> {
+        &self.locales
+    }
+
+    pub fn get_detected_languages(&self) -> &BTreeMap<String, String> {
+        &self.detected_languages
+    }
+
+    pub fn get_language_mismatches(&self) -> &[String] {
+        &self.language_mismatches
+    }
+
+    pub fn get_translations(&self) -> &BTreeMap<String, PersonTexts> {
+        &self.translations
+    }
+
+    // The profile text and the translations, to remove the personal
+    // information (see Redactor) and the boilerplate (see Boilerplate).
+    pub fn get_profile_texts_mut(
+        &mut self,
+    ) -> (&mut Option<String>, &mut BTreeMap<String, PersonTexts>) {
+        (&mut self.profile_info, &mut self.translations)
+    }
+
+    pub fn get_titles(&self) -> &[String] {
+        &self.titles
+    }
+
+    pub fn get_keywords(&self) -> &[String] {
+        &self.keywords
+    }
+
+    /// The name of the first orgunit, None if the person has none.
+    pub fn get_orgunit(&self) -> Option<&str> {
+        self.orgunits
+            .first()
+            .map(|orgunit| orgunit.get_name())
+            .filter(|name| !name.is_empty())
+    }
+
+    pub fn get_concepts(&self) -> &[WeightedConcept] {
+        &self.concepts
+    }
+
+    // The person fingerprint concepts, see ConceptIndex.
+    pub fn set_concepts(&mut self, concepts: Vec<WeightedConcept>) {
+        self.concepts = concepts;
+    }
+
+    pub fn get_orgunits(&self) -> &[OrgPath] {
+        &self.orgunits
+    }
+
+    // The full org paths, see OrgTree.
+    pub fn set_orgunits(&mut self, orgunits: Vec<OrgPath>) {
+        self.orgunits = orgunits;
+    }
 }
 
-// Another try_from, but this one takes a locale string and extracts the
-// profile_information for the specified locale.
+// Another try_from, but this one takes a chain of locales and extracts the
+// texts for the first locale which has them, see LocaleChain.
 //
 // Can we map the uuid already here? Probably not, there could be unknown
 // uuids at this point?
 impl PersonClean {
     pub fn try_from_with_locale_umap(
         value: &PersonJson,
-        locale: &str,
+        locale: &LocaleChain,
         umap: &mut UuidMap,
     ) -> Result<Self, CleanError> {
         let uuid = value.uuid.as_ref().ok_or(CleanError::MissingUUID)?;
@@ -133,30 +238,73 @@ impl PersonClean {
             .ok_or(CleanError::MissingLastName)?;
         let full_name = format!("{} {}", first_name, last_name);
 
+        let mut locales = BTreeMap::new();
+        let mut record = |field: &str, used: &str| {
+            locales.insert(field.to_string(), used.to_string());
+        };
+
         // Extract profile informations using locale? The function returns a vec,
         // which can be empty ([]).
-        let profile_info_text = value.get_profile_information_texts_for_locale(locale);
-        let profile_info_text = profile_info_text
-            .first() // First element of the vector (it should only contain one?).
-            .copied() // Dereferences &&str to &str.
-            .unwrap_or("There is no profile_information."); // TODO how to handle this?
-
-        let titles = value.get_titles_for_locale(locale);
+        // First element of the vector (it should only contain one?).
+        let profile_info_text = locale
+            .pick_text(|locale| {
+                value
+                    .get_profile_information_texts_for_locale(locale)
+                    .first()
+                    .copied()
+            })
+            .map(|(used, text)| {
+                record("profile_info", used);
+                text
+            });
+
+        let titles = match locale.pick_vec(|locale| value.get_titles_for_locale(locale)) {
+            Some((used, titles)) => {
+                record("titles", used);
+                titles
+            }
+            None => vec![],
+        };
         trace!("TITLES {:?}", titles);
 
-        let keywords = value.get_keywords_for_locale(locale);
+        let keywords = match locale.pick_vec(|locale| value.get_keywords_for_locale(locale)) {
+            Some((used, keywords)) => {
+                record("keywords", used);
+                keywords
+            }
+            None => vec![],
+        };
         trace!("KEYWORDS {:?}", keywords);
 
-        let orgname = value.get_organisationalunit_for_locale(locale);
-        trace!("ORGNAME {:?}", orgname);
+        let translations = if locale.is_bilingual() {
+            value.get_texts_per_locale(locale.locales())
+        } else {
+            BTreeMap::new()
+        };
+
+        let mut tags = LanguageTags::default();
+        if locale.detects_language() {
+            if let (Some(text), Some(declared)) = (profile_info_text, locales.get("profile_info")) {
+                tags.check("profile_info", text, Some(declared));
+            }
+        }
 
         // We have come this far, return the new struct.
         Ok(PersonClean {
             uuid: safe_uuid,
             name: full_name,
-            profile_info: profile_info_text.to_string(),
+            name_variants: value.get_name_variants(),
+            profile_info: profile_info_text.map(String::from),
             titles,
             keywords,
+            concepts: vec![],
+            // Which associations count depends on the reference date, the
+            // orgunits are set in load_persons().
+            orgunits: vec![],
+            locales,
+            translations,
+            detected_languages: tags.detected,
+            language_mismatches: tags.mismatches,
         })
     }
 }
@@ -620,11 +768,76 @@ impl PersonJson {
         ))
     }
 
+    // The nameVariants as "First Last", without duplicates of the name.
+    pub fn get_name_variants(&self) -> Vec<String> {
+        let name = self
+            .get_first_and_last_name()
+            .map(|(first, last)| format!("{} {}", first, last));
+        let mut variants: Vec<String> = vec![];
+        for variant in self.nameVariants.as_deref().unwrap_or(&[]) {
+            let Some(variant_name) = variant.name.as_ref() else {
+                continue;
+            };
+            let parts: Vec<&str> = [&variant_name.firstName, &variant_name.lastName]
+                .into_iter()
+                .filter_map(|part| part.as_deref())
+                .collect();
+            let full_name = parts.join(" ");
+            if !full_name.is_empty()
+                && Some(&full_name) != name.as_ref()
+                && !variants.contains(&full_name)
+            {
+                variants.push(full_name);
+            }
+        }
+        variants
+    }
+
     // The uuid, should always be present. Return a slice.
     pub fn get_uuid(&self) -> Option<&str> {
         self.uuid.as_deref()
     }
 
+    // The ORCID without the https://orcid.org/ part.
+    pub fn get_orcid(&self) -> Option<&str> {
+        let orcid = self.orcid.as_deref()?.trim();
+        orcid.rsplit('/').next().filter(|orcid| !orcid.is_empty())
+    }
+
+    // The email addresses from all the staff associations.
+    pub fn get_emails(&self) -> Vec<&str> {
+        self.staffOrganisationAssociations
+            .as_deref()
+            .unwrap_or(&[])
+            .iter()
+            .flat_map(|association| association.emails.as_deref().unwrap_or(&[]))
+            .filter_map(|email| email.value.as_ref()?.value.as_deref())
+            .collect()
+    }
+
+    // The phone numbers from all the staff associations.
+    pub fn get_phone_numbers(&self) -> Vec<&str> {
+        self.staffOrganisationAssociations
+            .as_deref()
+            .unwrap_or(&[])
+            .iter()
+            .flat_map(|association| association.phoneNumbers.as_deref().unwrap_or(&[]))
+            .filter_map(|phone| phone.value.as_ref()?.value.as_deref())
+            .collect()
+    }
+
+    // The street and address lines from all the staff associations.
+    pub fn get_addresses(&self) -> Vec<&str> {
+        self.staffOrganisationAssociations
+            .as_deref()
+            .unwrap_or(&[])
+            .iter()
+            .flat_map(|association| association.addresses.as_deref().unwrap_or(&[]))
+            .flat_map(|address| [address.street.as_deref(), address.addressLines.as_deref()])
+            .flatten()
+            .collect()
+    }
+
     // Profile info text in difference locales. All values are Option<T> in the
     // struct, hence the large number of "if let Some(...)"s.
     /*
@@ -775,10 +988,71 @@ impl PersonJson {
         keywords
     }
 
+    /// The (uuid, name) of the organisational units in the staff
+    /// associations, without duplicates. Associations which have ended
+    /// before the reference date (YYYY-MM-DD) are skipped.
+    pub fn get_orgunits_for_locale(
+        &self,
+        locale: &str,
+        reference_date: &str,
+    ) -> Vec<(&str, Option<&str>)> {
+        let mut orgunits: Vec<(&str, Option<&str>)> = vec![];
+        for association in self.staffOrganisationAssociations.as_deref().unwrap_or(&[]) {
+            let ended = association
+                .period
+                .as_ref()
+                .and_then(|period| period.endDate.as_deref())
+                .is_some_and(|end_date| end_date.get(..10).unwrap_or(end_date) < reference_date);
+            let Some(unit) = association.organisationalUnit.as_ref() else {
+                continue;
+            };
+            let Some(uuid) = unit.uuid.as_deref() else {
+                continue;
+            };
+            if ended || orgunits.iter().any(|(u, _)| *u == uuid) {
+                continue;
+            }
+            let name = unit
+                .name
+                .as_ref()
+                .and_then(|name| name.text.as_deref())
+                .and_then(|texts| {
+                    texts
+                        .iter()
+                        .find(|lt| lt.locale.as_deref() == Some(locale))
+                        .or(texts.first())
+                })
+                .and_then(|lt| lt.value.as_deref());
+            orgunits.push((uuid, name));
+        }
+        orgunits
+    }
+
+    /// The profile text, titles and keywords in each of the locales, the
+    /// locales without any of them are left out.
+    pub fn get_texts_per_locale(&self, locales: &[String]) -> BTreeMap<String, PersonTexts> {
+        locales
+            .iter()
+            .filter_map(|locale| {
+                let texts = PersonTexts {
+                    profile_info: self
+                        .get_profile_information_texts_for_locale(locale)
+                        .first()
+                        .map(|text| text.to_string()),
+                    titles: self.get_titles_for_locale(locale),
+                    keywords: self.get_keywords_for_locale(locale),
+                };
+                (texts != PersonTexts::default()).then(|| (locale.clone(), texts))
+            })
+            .collect()
+    }
+
     // pub organisationalUnit: Option<OrganisationalUnit>,
     // pub struct OrganisationalUnit {
     //     pub name: Option<FormattedText>,
-    pub fn get_organisationalunit_for_locale(&self, locale: &str) -> &str {
+    /// The name of the organisational unit of the first staff association
+    /// with a name in the locale, None if there is none.
+    pub fn get_organisationalunit_for_locale(&self, locale: &str) -> Option<&str> {
         if let Some(associations) = &self.staffOrganisationAssociations {
             for association in associations {
                 if let Some(unit) = &association.organisationalUnit {
@@ -788,7 +1062,7 @@ impl PersonJson {
                                 if let Some(loc) = &locale_text.locale {
                                     if loc == locale {
                                         if let Some(value) = &locale_text.value {
-                                            return value;
+                                            return Some(value);
                                         }
                                     }
                                 }
@@ -798,64 +1072,22 @@ impl PersonJson {
                 }
             }
         }
-        "no organisation unit"
+        None
     }
 }
 
 // ----
 
 // This always returns, but the result could be an empty vector.
+impl JsonlRecord for PersonJson {
+    const KIND: &'static str = "persons";
+}
+
 pub fn read_persons_jsonl(
     file_path: &str,
-    umap: &UuidMap,
-) -> Result<Vec<PersonJson>, Box<dyn std::error::Error>> {
-    let file = File::open(file_path)?;
-    let reader = BufReader::new(file);
-    let data = Arc::new(Mutex::new(vec![]));
-    let failed_count = Arc::new(Mutex::new(0));
-
-    reader
-        .lines()
-        .map_while(Result::ok)
-        .par_bridge() // parallelise
-        // expect to check if it works, for prod use ok().
-        //.filter_map(|line: String| serde_json::from_str(&line).expect("Err")) // filter out bad lines
-        //.filter_map(|line: String| serde_json::from_str(&line).ok()) // filter out bad lines
-        .for_each(|line: String| {
-            match serde_json::from_str::<PersonJson>(&line) {
-                Ok(json) => {
-                    debug!("uuid={:?}", json.uuid);
-
-                    // HACK temp for testing PJB
-                    if let Some(get_uuid) = json.get_uuid() {
-                        if umap.optout_contains(get_uuid) {
-                            warn!("Opt-out person uuid in person data!");
-                        } else {
-                            // Add it to the data vector.
-                            let mut data = data.lock().unwrap();
-                            data.push(json);
-                        }
-                    }
-                }
-                Err(e) => {
-                    error!("{}", e);
-                    //panic!("{}", line);
-
-                    // Increment the failure counter.
-                    let mut failed = failed_count.lock().unwrap();
-                    *failed += 1;
-                }
-            }
-        });
-
-    if *failed_count.lock().unwrap() > 0 {
-        warn!("Failed to parse {} lines.", *failed_count.lock().unwrap());
-    }
-
-    // Extract the data from Arc<Mutex<...>> and return it.
-    let extracted_data = Arc::try_unwrap(data).unwrap().into_inner().unwrap();
-    info!("Extracted {} entries.", extracted_data.len());
-    Ok(extracted_data)
+    rejects: &mut Rejects,
+) -> Result<Vec<PersonJson>, LucrisError> {
+    read_all(JsonlReader::<PersonJson>::open(file_path)?, rejects)
 }
 
 #[cfg(test)]
@@ -923,8 +1155,74 @@ mod tests {
         println!("{}", person_des_jstr);
         assert_eq!(
             person_des_jstr,
-            r#"{"uuid":"01234567-0123-0123-0123-0123456789AB","name":"Quinten Berck","profile_info":"","titles":[],"keywords":[]}"#
+            r#"{"uuid":"01234567-0123-0123-0123-0123456789AB","name":"Quinten Berck","name_variants":[],"profile_info":null,"titles":[],"keywords":[],"concepts":[],"orgunits":[]}"#
+        );
+    }
+
+    #[test]
+    fn test_person_name_variants() {
+        let data = r#"
+        {
+          "uuid": "01234567-0123-0123-0123-0123456789AB",
+          "name": {
+            "firstName": "Quinten",
+            "lastName": "Berck"
+          },
+          "nameVariants": [
+            {"name": {"firstName": "Quinten", "lastName": "Berck"}},
+            {"name": {"firstName": "Q.", "lastName": "Berck"}},
+            {"name": {"lastName": "Berck-Larsson"}}
+          ]
+        }
+        "#;
+        let person: PersonJson = serde_json::from_str(data).expect("Err");
+        let person_des: PersonClean = PersonClean::try_from(&person).expect("Err");
+        assert_eq!(
+            person_des.get_name_variants(),
+            ["Q. Berck", "Berck-Larsson"]
+        );
+    }
+
+    #[test]
+    fn test_orgunits_reference_date() {
+        let data = r#"
+        {
+          "uuid": "01234567-0123-0123-0123-0123456789AB",
+          "staffOrganisationAssociations": [
+            {"organisationalUnit": {"uuid": "a0000000-0000-0000-0000-000000000003",
+              "name": {"text": [{"locale": "en_GB", "value": "Faculty of Humanities (old)"}]}},
+             "period": {"startDate": "1990-01-01T12:00:00.000+0100",
+              "endDate": "1999-12-31T12:00:00.000+0100"}},
+            {"organisationalUnit": {"uuid": "0463eb96-7994-4860-a9b6-baff3ecf2ff6",
+              "name": {"text": [{"locale": "en_GB", "value": "Lund University Humanities Lab"}]}},
+             "period": {"startDate": "2006-01-01T12:00:00.000+0100"}}
+          ]
+        }
+        "#;
+        let person: PersonJson = serde_json::from_str(data).expect("Err");
+        let names = |date: &str| -> Vec<Option<&str>> {
+            person
+                .get_orgunits_for_locale("en_GB", date)
+                .into_iter()
+                .map(|(_, name)| name)
+                .collect()
+        };
+        assert_eq!(
+            names("1999-12-31"),
+            [
+                Some("Faculty of Humanities (old)"),
+                Some("Lund University Humanities Lab")
+            ]
+        );
+        assert_eq!(
+            names("2025-01-01"),
+            [Some("Lund University Humanities Lab")]
+        );
+        assert_eq!(
+            person.get_organisationalunit_for_locale("en_GB"),
+            Some("Faculty of Humanities (old)")
         );
+        assert_eq!(person.get_organisationalunit_for_locale("sv_SE"), None);
     }
 
     #[test]
@@ -991,12 +1289,13 @@ mod tests {
         // Create and save the safe_uuid so we can compare it later.
         let safe_uuid = umap.add_uuid("01234567-0123-0123-0123-0123456789AB");
         let answer = format!(
-            r#"{{"uuid":"{}","name":"Quinten Berck","profile_info":"Research Engineer, Lund University Humanities Lab","titles":[],"keywords":[]}}"#,
+            r#"{{"uuid":"{}","name":"Quinten Berck","name_variants":[],"profile_info":"Research Engineer, Lund University Humanities Lab","titles":[],"keywords":[],"concepts":[],"orgunits":[],"locales":{{"profile_info":"en_GB"}}}}"#,
             safe_uuid
         );
         let person: PersonJson = serde_json::from_str(data).expect("Err");
         let person_des =
-            PersonClean::try_from_with_locale_umap(&person, "en_GB", &mut umap).expect("Err");
+            PersonClean::try_from_with_locale_umap(&person, &"en_GB".into(), &mut umap)
+                .expect("Err");
         let person_des_jstr = serde_json::to_string(&person_des).unwrap();
         assert_eq!(person_des_jstr, answer);
     }
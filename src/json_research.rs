@@ -1,16 +1,15 @@
 #![allow(non_snake_case)]
-use crate::errors::CleanError;
+use crate::concept_index::WeightedConcept;
+use crate::errors::{CleanError, LucrisError, Rejects};
+use crate::jsonl::{read_all, JsonlReader, JsonlRecord};
+use crate::language::LanguageTags;
+use crate::locale::LocaleChain;
+use crate::org_tree::OrgPath;
 use crate::uuid_map::UuidMap;
-use log::{debug, error, info, trace, warn};
-use rayon::iter::ParallelBridge;
-use rayon::iter::ParallelIterator;
+use log::{debug, trace, warn};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::fmt;
-use std::fs::File as FSFile;
-use std::io::BufRead;
-use std::io::BufReader;
-use std::sync::{Arc, Mutex};
 
 /// JSON as it is read from the AIML cleaned data.
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
@@ -100,8 +99,10 @@ pub struct ResearchJson {
 pub struct ResearchClean {
     uuid: String,
     pub title: String,
+    // None when there is no abstract, or it is boilerplate, see
+    // Boilerplate.
     #[serde(rename = "abstract")]
-    pub abstract_text: String,
+    pub abstract_text: Option<String>,
     pub keywords: Vec<String>,
     pub persons: Vec<PersonRef>, // Or PersonClean?
     //#[serde(rename = "creationDate")]
@@ -109,6 +110,203 @@ pub struct ResearchClean {
     //#[serde(rename = "publicationDate")]
     // Publication date is a bit more complex. Often only year.
     publication_date: String,
+    // The part of the type uri after researchoutputtypes/, for example
+    // "contributiontojournal/article".
+    research_type: String,
+    peer_review: Option<bool>,
+    // The language code from the language uri, for example "en_GB".
+    language: String,
+    // Top concepts from the fingerprints, filled in when loading.
+    pub concepts: Vec<WeightedConcept>,
+    // Managing unit first. The full paths are filled in when loading.
+    pub orgunits: Vec<OrgPath>,
+    // The locale each text field ("abstract", "keywords") was taken from,
+    // see LocaleChain.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub locales: BTreeMap<String, String>,
+    // The texts per locale, only in bilingual mode.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub translations: BTreeMap<String, ResearchTexts>,
+    // The detected language ("title", "abstract") and the fields where it
+    // differs from the declared locale, see LanguageTags.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub detected_languages: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub language_mismatches: Vec<String>,
+    // Where it was published.
+    #[serde(skip_serializing_if = "Venue::is_empty")]
+    pub venue: Venue,
+    // The DOIs, ISBNs, external id and links, normalised.
+    #[serde(skip_serializing_if = "Identifiers::is_empty")]
+    pub identifiers: Identifiers,
+    // The safe uuids of the duplicates merged into this one, see dedup.rs.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub duplicates: Vec<String>,
+    // The position in the research file, filled in when loading. Used for
+    // the "source" output order, not exported.
+    #[serde(skip)]
+    pub source_index: usize,
+}
+
+/// The texts of a research output in one locale.
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct ResearchTexts {
+    #[serde(rename = "abstract")]
+    pub abstract_text: Option<String>,
+    pub keywords: Vec<String>,
+}
+
+/// The identifiers of a research output. The DOIs are lower case without
+/// the resolver ("10.1023/a:1024032218981"), the ISBNs (print and
+/// electronic) digits only, the external id is "source:id".
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct Identifiers {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dois: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub isbns: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
+    // The links of the electronic versions (not the DOI links).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub links: Vec<String>,
+}
+
+impl Identifiers {
+    pub fn is_empty(&self) -> bool {
+        self.dois.is_empty()
+            && self.isbns.is_empty()
+            && self.external_id.is_none()
+            && self.links.is_empty()
+    }
+
+    /// A link to the research output: the first DOI, or else the first
+    /// electronic version link.
+    pub fn url(&self) -> Option<String> {
+        match self.dois.first() {
+            Some(doi) => Some(format!("https://doi.org/{}", doi)),
+            None => self.links.first().cloned(),
+        }
+    }
+
+    // Adds the identifiers of a merged duplicate.
+    pub fn merge(&mut self, other: &Identifiers) {
+        for doi in &other.dois {
+            if !self.dois.contains(doi) {
+                self.dois.push(doi.clone());
+            }
+        }
+        for isbn in &other.isbns {
+            if !self.isbns.contains(isbn) {
+                self.isbns.push(isbn.clone());
+            }
+        }
+        if self.external_id.is_none() {
+            self.external_id = other.external_id.clone();
+        }
+        for link in &other.links {
+            if !self.links.contains(link) {
+                self.links.push(link.clone());
+            }
+        }
+    }
+}
+
+/// Where a research output was published. The journal for articles, the
+/// event for conference contributions, the host publication (book or
+/// proceedings) for chapters.
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct Venue {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub journal: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issn: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host_publication: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub publisher: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume: Option<String>,
+    // The journal number.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issue: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pages: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub article_number: Option<String>,
+}
+
+impl Venue {
+    pub fn is_empty(&self) -> bool {
+        *self == Venue::default()
+    }
+}
+
+// A short citation, "Biotechnology Letters 25(12): 927-933", for the text
+// outputs.
+impl fmt::Display for Venue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = [&self.journal, &self.host_publication, &self.event]
+            .into_iter()
+            .find_map(|name| name.as_deref());
+        let mut parts = vec![];
+        if let Some(name) = name {
+            parts.push(name.to_string());
+        }
+        match (&self.volume, &self.issue) {
+            (Some(volume), Some(issue)) => parts.push(format!("{}({})", volume, issue)),
+            (Some(volume), None) => parts.push(volume.clone()),
+            (None, Some(issue)) => parts.push(format!("({})", issue)),
+            (None, None) => {}
+        }
+        let mut citation = parts.join(" ");
+        if let Some(pages) = self.pages.as_ref().or(self.article_number.as_ref()) {
+            citation = format!("{}: {}", citation, pages);
+        }
+        if let Some(publisher) = &self.publisher {
+            if citation.is_empty() {
+                citation = publisher.clone();
+            } else {
+                citation = format!("{}, {}", citation, publisher);
+            }
+        }
+        write!(f, "{}", citation.trim_start_matches(": "))
+    }
+}
+
+// The text of a Term in the first locale of the chain which has it, or
+// else in any locale.
+fn term_for_locale(term: &Term, locale: &LocaleChain) -> Option<String> {
+    let text = |wanted: &str| {
+        term.text
+            .iter()
+            .find(|text| text.locale.as_deref() == Some(wanted))
+            .and_then(|text| text.value.as_deref())
+    };
+    locale
+        .pick(text)
+        .map(|(_, value)| value)
+        .or_else(|| term.text.iter().find_map(|text| text.value.as_deref()))
+        .map(String::from)
+}
+
+// "https://doi.org/10.1023/A:1024032218981" -> "10.1023/a:1024032218981".
+pub fn normalise_doi(doi: &str) -> Option<String> {
+    let doi = doi.trim().to_lowercase();
+    let start = doi.find("10.")?;
+    Some(doi[start..].to_string())
+}
+
+// "978-91-7753-123-4" -> "9789177531234", None if it is not 10 or 13 long.
+pub fn normalise_isbn(isbn: &str) -> Option<String> {
+    let isbn: String = isbn
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == 'X' || *c == 'x')
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    matches!(isbn.len(), 10 | 13).then_some(isbn)
 }
 
 /// Whether a researcher is internal (we have info in persons.jsonl) or external.
@@ -146,6 +344,10 @@ impl PersonRef {
         self.inex == PersonType::Internal
     }
 
+    pub fn is_external(&self) -> bool {
+        self.inex == PersonType::External
+    }
+
     // Creators.
     pub fn internal(idx: u32, uuid: impl Into<String>, name: impl Into<String>) -> Self {
         Self {
@@ -179,11 +381,18 @@ impl PersonRef {
 pub struct ResearchCleanBuilder {
     uuid: String,
     title: String,
-    abstract_text: String,
+    abstract_text: Option<String>,
     keywords: Vec<String>,
     persons: Vec<PersonRef>,
     creation_date: String,
     publication_date: String,
+    research_type: String,
+    peer_review: Option<bool>,
+    language: String,
+    concepts: Vec<WeightedConcept>,
+    orgunits: Vec<OrgPath>,
+    venue: Venue,
+    identifiers: Identifiers,
 }
 
 impl ResearchClean {
@@ -191,11 +400,18 @@ impl ResearchClean {
         ResearchCleanBuilder {
             uuid: uuid.into(),
             title: String::new(),
-            abstract_text: String::new(),
+            abstract_text: None,
             keywords: Vec::new(),
             persons: Vec::new(),
             creation_date: String::new(),
             publication_date: String::new(),
+            research_type: String::new(),
+            peer_review: None,
+            language: String::new(),
+            concepts: Vec::new(),
+            orgunits: Vec::new(),
+            venue: Venue::default(),
+            identifiers: Identifiers::default(),
         }
     }
 }
@@ -207,7 +423,7 @@ impl ResearchCleanBuilder {
     }
 
     pub fn abstract_text(mut self, abstract_text: impl Into<String>) -> Self {
-        self.abstract_text = abstract_text.into();
+        self.abstract_text = Some(abstract_text.into());
         self
     }
 
@@ -231,6 +447,41 @@ impl ResearchCleanBuilder {
         self
     }
 
+    pub fn research_type(mut self, research_type: impl Into<String>) -> Self {
+        self.research_type = research_type.into();
+        self
+    }
+
+    pub fn peer_review(mut self, peer_review: Option<bool>) -> Self {
+        self.peer_review = peer_review;
+        self
+    }
+
+    pub fn language(mut self, language: impl Into<String>) -> Self {
+        self.language = language.into();
+        self
+    }
+
+    pub fn venue(mut self, venue: Venue) -> Self {
+        self.venue = venue;
+        self
+    }
+
+    pub fn identifiers(mut self, identifiers: Identifiers) -> Self {
+        self.identifiers = identifiers;
+        self
+    }
+
+    pub fn concepts(mut self, concepts: Vec<WeightedConcept>) -> Self {
+        self.concepts = concepts;
+        self
+    }
+
+    pub fn orgunits(mut self, orgunits: Vec<OrgPath>) -> Self {
+        self.orgunits = orgunits;
+        self
+    }
+
     pub fn build(self) -> ResearchClean {
         ResearchClean {
             uuid: self.uuid,
@@ -240,6 +491,19 @@ impl ResearchCleanBuilder {
             persons: self.persons,
             creation_date: self.creation_date,
             publication_date: self.publication_date,
+            research_type: self.research_type,
+            peer_review: self.peer_review,
+            language: self.language,
+            concepts: self.concepts,
+            orgunits: self.orgunits,
+            locales: BTreeMap::new(),
+            translations: BTreeMap::new(),
+            detected_languages: BTreeMap::new(),
+            language_mismatches: vec![],
+            venue: self.venue,
+            identifiers: self.identifiers,
+            duplicates: vec![],
+            source_index: 0,
         }
     }
 }
@@ -286,8 +550,8 @@ impl ResearchClean {
         &self.title
     }
 
-    pub fn get_abstract(&self) -> &str {
-        &self.abstract_text
+    pub fn get_abstract(&self) -> Option<&str> {
+        self.abstract_text.as_deref()
     }
 
     pub fn get_uuid(&self) -> &str {
@@ -305,64 +569,85 @@ impl ResearchClean {
     pub fn get_publication_date(&self) -> &str {
         &self.publication_date
     }
+
+    pub fn get_research_type(&self) -> &str {
+        &self.research_type
+    }
+
+    pub fn get_peer_review(&self) -> Option<bool> {
+        self.peer_review
+    }
+
+    pub fn get_language(&self) -> &str {
+        &self.language
+    }
+
+    pub fn get_concepts(&self) -> &[WeightedConcept] {
+        &self.concepts
+    }
+
+    pub fn get_orgunits(&self) -> &[OrgPath] {
+        &self.orgunits
+    }
 }
 
-// This one takes a locale string and extracts the information for the specified locale.
+// This one takes a chain of locales and extracts the texts for the first
+// locale which has them, see LocaleChain.
 impl ResearchClean {
     pub fn try_from_with_locale_umap(
         value: &ResearchJson,
-        locale: &str,
+        locale: &LocaleChain,
         umap: &mut UuidMap,
     ) -> Result<Self, CleanError> {
         let uuid = value.uuid.as_ref().ok_or(CleanError::MissingUUID)?;
 
-        let (abstract_title, abstract_text) = value.get_title_abstract(locale); // returns &str, &str
+        // The title has no locale.
+        let abstract_title = value.get_title_value().unwrap_or("");
+        let mut locales = BTreeMap::new();
+        let abstract_text = locale
+            .pick_text(|locale| value.get_abstract_text_for_locale(locale))
+            .map(|(used, text)| {
+                locales.insert("abstract".to_string(), used.to_string());
+                text
+            });
         let mut persons: Vec<PersonRef> = vec![];
         let person_names = value.get_internal_person_names(); // People responsible for the research.
         let mut c = 0;
+        // The opted-out persons have already been removed, see OptOutPolicy.
         for (first_name, last_name, uuid) in person_names.iter() {
-            if umap.optout_contains(uuid) {
-                warn!("Opt-out internal person uuid in research!");
-            } else {
-                let safe_uuid = umap.get_uuid_as_str(uuid);
-                // Often more than one.
-                let person = PersonRef {
-                    idx: c,
-                    uuid: safe_uuid,
-                    name: format!("{} {}", first_name, last_name),
-                    inex: PersonType::Internal,
-                };
-                persons.push(person);
-                c += 1;
-            }
+            let safe_uuid = umap.get_uuid_as_str(uuid);
+            // Often more than one.
+            let person = PersonRef {
+                idx: c,
+                uuid: safe_uuid,
+                name: format!("{} {}", first_name, last_name),
+                inex: PersonType::Internal,
+            };
+            persons.push(person);
+            c += 1;
         }
 
         let external_person_names = value.get_external_person_names();
         for (full_name, uuid) in external_person_names.iter() {
             let safe_uuid = umap.get_uuid_as_str(uuid);
-            if umap.optout_contains(uuid) {
-                warn!("Opt-out external person uuid in research!");
-            } else {
-                let person = PersonRef {
-                    idx: c,
-                    uuid: safe_uuid,
-                    name: full_name.to_string(),
-                    inex: PersonType::External,
-                };
-                persons.push(person);
-                c += 1;
-            }
+            let person = PersonRef {
+                idx: c,
+                uuid: safe_uuid,
+                name: full_name.to_string(),
+                inex: PersonType::External,
+            };
+            persons.push(person);
+            c += 1;
         }
 
         // Some journals (?) have a different persons sections, without
         // uuids. (They do have pure_ids however, but these are unused at the
         // moment). This extracts those names without uuids.
         // Also unpublished works?
+        // The opted-out names have already been removed, see OptOutPolicy.
         if persons.is_empty() {
-            // HACK
-            // FIX this will allow opt-out persons because we do not have uuid?
             warn!("Empty persons in {}.", uuid);
-            for full_name in value.get_names_umap(umap) {
+            for full_name in value.get_names() {
                 trace!("full_name: {}", full_name);
                 // We can generate a "fake" uuid, which will not be present
                 // in the persons data. Not sure if good or bad...
@@ -381,7 +666,32 @@ impl ResearchClean {
         // safe_uuid for the research structure itself.
         let safe_uuid = umap.get_uuid_as_str(uuid);
 
-        let keywords = value.get_keywords_for_locale(locale);
+        let keywords = match locale.pick_vec(|locale| value.get_keywords_for_locale(locale)) {
+            Some((used, keywords)) => {
+                locales.insert("keywords".to_string(), used.to_string());
+                keywords
+            }
+            None => vec![],
+        };
+
+        let translations = if locale.is_bilingual() {
+            value.get_texts_per_locale(locale.locales())
+        } else {
+            BTreeMap::new()
+        };
+
+        // The title is declared in the language of the research output.
+        let mut tags = LanguageTags::default();
+        if locale.detects_language() {
+            tags.check("title", abstract_title, Some(value.get_language_code()));
+            if let Some(abstract_text) = abstract_text {
+                tags.check(
+                    "abstract",
+                    abstract_text,
+                    locales.get("abstract").map(String::as_str),
+                );
+            }
+        }
 
         let creation_date = value.get_creation_date().to_owned();
 
@@ -391,11 +701,28 @@ impl ResearchClean {
         Ok(ResearchClean {
             uuid: safe_uuid,
             title: abstract_title.to_string(),
-            abstract_text: abstract_text.to_string(),
+            abstract_text: abstract_text.map(String::from),
             persons,
             keywords,
             creation_date,
             publication_date,
+            research_type: value.get_research_type().to_owned(),
+            peer_review: value.peerReview,
+            language: value.get_language_code().to_owned(),
+            concepts: vec![],
+            orgunits: value
+                .get_orgunits_for_locale(locale.primary())
+                .into_iter()
+                .map(|(uuid, name)| OrgPath::unresolved(uuid, name))
+                .collect(),
+            locales,
+            translations,
+            detected_languages: tags.detected,
+            language_mismatches: tags.mismatches,
+            venue: value.get_venue(locale),
+            identifiers: value.get_identifiers(),
+            duplicates: vec![],
+            source_index: 0,
         })
     }
 }
@@ -893,8 +1220,6 @@ impl ResearchJson {
     // Get the first and last names, plus associated uuid, from the
     // personAssociations data.
     pub fn get_internal_person_names(&self) -> Vec<(&str, &str, &str)> {
-        // umap?
-        //todo!("Fix opt-out check");
         self.personAssociations
             .as_ref()
             .map(|associations| {
@@ -911,28 +1236,6 @@ impl ResearchJson {
             .unwrap_or_default()
     }
 
-    pub fn get_internal_person_names_umap(&self, umap: &UuidMap) -> Vec<(&str, &str, &str)> {
-        //todo!("Fix opt-out check");
-        self.personAssociations
-            .as_ref()
-            .map(|associations| {
-                associations
-                    .iter()
-                    .filter_map(|association| {
-                        let first_name = association.name.as_ref()?.firstName.as_deref()?;
-                        let last_name = association.name.as_ref()?.lastName.as_deref()?;
-                        let uuid = association.person.as_ref()?.uuid.as_deref()?;
-                        if umap.optout_contains(uuid) {
-                            None
-                        } else {
-                            Some((first_name, last_name, uuid))
-                        }
-                    })
-                    .collect()
-            })
-            .unwrap_or_default()
-    }
-
     // Get the name(s) and UUID of the externalPersons from the personAssociations data.
     pub fn get_external_person_names(&self) -> Vec<(&str, &str)> {
         self.personAssociations
@@ -957,37 +1260,6 @@ impl ResearchJson {
     }
 
     // These are present sometimes as contributors to journals WITHOUT uuids etc.
-    pub fn get_names_umap(&self, umap: &UuidMap) -> Vec<String> {
-        self.personAssociations
-            .as_ref()
-            .unwrap_or(&Vec::new())
-            .iter()
-            .filter_map(|association| {
-                let name = association.name.as_ref()?;
-                trace!("{:?}", name);
-                if let (Some(first), Some(last)) =
-                    (name.firstName.as_deref(), name.lastName.as_deref())
-                {
-                    let uuid = association
-                        .person
-                        .as_ref()
-                        .and_then(|p| p.uuid.as_deref())
-                        .or_else(|| association.externalPerson.as_ref()?.uuid.as_deref())
-                        .unwrap_or("");
-                    trace!("Uuid {{ {} }}", uuid); // {{ is an escaped {...
-                    if umap.optout_contains(uuid) {
-                        warn!("Opt-out person in data!");
-                        None
-                    } else {
-                        Some(format!("{} {}", first, last))
-                    }
-                } else {
-                    None
-                }
-            })
-            .collect()
-    }
-
     pub fn get_names(&self) -> Vec<String> {
         self.personAssociations
             .as_ref()
@@ -1018,6 +1290,21 @@ impl ResearchJson {
         (title_text, abstract_text)
     }
 
+    /// The abstract and keywords in each of the locales, the locales
+    /// without either are left out.
+    pub fn get_texts_per_locale(&self, locales: &[String]) -> BTreeMap<String, ResearchTexts> {
+        locales
+            .iter()
+            .filter_map(|locale| {
+                let texts = ResearchTexts {
+                    abstract_text: self.get_abstract_text_for_locale(locale).map(String::from),
+                    keywords: self.get_keywords_for_locale(locale),
+                };
+                (texts != ResearchTexts::default()).then(|| (locale.clone(), texts))
+            })
+            .collect()
+    }
+
     pub fn is_approved(&self) -> bool {
         self.workflow
             .as_ref()
@@ -1072,6 +1359,131 @@ impl ResearchJson {
             .unwrap_or("")
     }
 
+    /// The (uuid, name) of the managing organisational unit and the other
+    /// organisational units, without duplicates. The uuids are the LUCRIS
+    /// orgunit uuids, see OrgTree.
+    pub fn get_orgunits_for_locale(&self, locale: &str) -> Vec<(&str, Option<&str>)> {
+        let mut orgunits: Vec<(&str, Option<&str>)> = vec![];
+        let units = self
+            .managingOrganisationalUnit
+            .iter()
+            .chain(self.organisationalUnits.as_deref().unwrap_or(&[]));
+        for unit in units {
+            let Some(uuid) = unit.uuid.as_deref() else {
+                continue;
+            };
+            if orgunits.iter().any(|(u, _)| *u == uuid) {
+                continue;
+            }
+            let name = unit.name.as_ref().and_then(|name| {
+                name.text
+                    .iter()
+                    .find(|lv| lv.locale.as_deref() == Some(locale))
+                    .or(name.text.first())
+                    .and_then(|lv| lv.value.as_deref())
+            });
+            orgunits.push((uuid, name));
+        }
+        orgunits
+    }
+
+    // The type uri without the /dk/atira/pure/researchoutput/researchoutputtypes/
+    // prefix, e.g. "contributiontojournal/article". Returns "" if empty.
+    pub fn get_research_type(&self) -> &str {
+        let uri = self
+            .type_field
+            .as_ref()
+            .and_then(|t| t.uri.as_deref())
+            .unwrap_or("");
+        match uri.split_once("researchoutputtypes/") {
+            Some((_, research_type)) => research_type,
+            None => uri.rsplit('/').next().unwrap_or(""),
+        }
+    }
+
+    /// The journal, event, host publication and publisher, the names in
+    /// the first locale of the chain which has them.
+    pub fn get_venue(&self, locale: &LocaleChain) -> Venue {
+        let journal = self.journalAssociation.as_ref();
+        let non_empty = |value: &Option<String>| {
+            value
+                .as_deref()
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(String::from)
+        };
+        Venue {
+            journal: journal
+                .and_then(|journal| journal.title.as_ref())
+                .and_then(|title| non_empty(&title.value)),
+            issn: journal
+                .and_then(|journal| journal.issn.as_ref())
+                .and_then(|issn| non_empty(&issn.value)),
+            event: self
+                .event
+                .as_ref()
+                .and_then(|event| term_for_locale(event.name.as_ref()?, locale)),
+            host_publication: self
+                .hostPublicationTitle
+                .as_ref()
+                .map(|title| title.value.trim())
+                .filter(|title| !title.is_empty())
+                .map(String::from),
+            publisher: self
+                .publisher
+                .as_ref()
+                .and_then(|publisher| term_for_locale(publisher.name.as_ref()?, locale)),
+            volume: non_empty(&self.volume),
+            issue: non_empty(&self.journalNumber),
+            pages: non_empty(&self.pages),
+            article_number: non_empty(&self.articleNumber),
+        }
+    }
+
+    /// The DOIs of the electronic versions, the print and electronic
+    /// ISBNs, and the external id, normalised.
+    pub fn get_identifiers(&self) -> Identifiers {
+        let mut identifiers = Identifiers::default();
+        let dois = self
+            .electronicVersions
+            .iter()
+            .flatten()
+            .filter_map(|version| version.doi.as_deref().and_then(normalise_doi));
+        let isbns = self
+            .isbns
+            .iter()
+            .chain(self.electronicIsbns.iter())
+            .flatten()
+            .filter_map(|isbn| normalise_isbn(isbn));
+        let links = self
+            .electronicVersions
+            .iter()
+            .flatten()
+            .filter_map(|version| version.link.clone());
+        identifiers.merge(&Identifiers {
+            dois: dois.collect(),
+            isbns: isbns.collect(),
+            external_id: None,
+            links: links.collect(),
+        });
+        identifiers.external_id =
+            match (self.externalIdSource.as_deref(), self.externalId.as_deref()) {
+                (Some(source), Some(id)) => Some(format!("{}:{}", source, id)),
+                (None, Some(id)) => Some(id.to_string()),
+                _ => None,
+            };
+        identifiers
+    }
+
+    // The last part of the language uri, e.g. "en_GB". Returns "" if empty.
+    pub fn get_language_code(&self) -> &str {
+        self.language
+            .as_ref()
+            .and_then(|language| language.uri.as_deref())
+            .and_then(|uri| uri.rsplit('/').next())
+            .unwrap_or("")
+    }
+
     // Returns a string based on whatever is present in the data.
     // Returns "" if empty.
     pub fn get_current_publication_date(&self) -> String {
@@ -1148,91 +1560,26 @@ pub fn _dump_titles(research_data: &Vec<ResearchJson>, locale: &str) {
 
 // ----------------------------------------------------------------------------
 
-pub fn read_research_jsonl(
-    file_path: &str,
-    umap: &UuidMap,
-) -> Result<(Vec<ResearchJson>, HashMap<String, Vec<String>>), Box<dyn std::error::Error>> {
-    let file = FSFile::open(file_path)?;
-    let reader = BufReader::new(file);
-    let data = Arc::new(Mutex::new(vec![]));
-    let failed_count = Arc::new(Mutex::new(0));
-    let person_research: Arc<Mutex<HashMap<String, Vec<String>>>> =
-        Arc::new(Mutex::new(HashMap::new()));
-
-    reader
-        .lines()
-        .map_while(Result::ok)
-        .par_bridge() // parallelise
-        .for_each(|line: String| {
-            match serde_json::from_str::<ResearchJson>(&line) {
-                Ok(json) => {
-                    //trace!("title={:?}", json.title.clone().unwrap().value);
-                    debug!("research uuid={:?}", json.uuid);
-                    trace!("{:?}", json); // This generates a lot of output...
-
-                    // We check for the "approved" workflowStep marker.
-                    if !json.is_approved() {
-                        debug!("Ignoring unapproved research item {:?}", json.uuid);
-                        return;
-                    }
+// The person -> [research, ...] index is built by Combined, after the uuids
+// have been translated to safe uuids.
+// Only the approved research outputs are read.
+impl JsonlRecord for ResearchJson {
+    const KIND: &'static str = "research";
 
-                    // TODO check for optout uuid here? Ignore if it is?
-                    // We might want to do this more dynamically later...
-
-                    // Check persons for research reverse index?
-                    let mut map = person_research.lock().unwrap();
-                    let uuid = json.uuid.clone().unwrap();
-                    let persons = json.get_internal_person_names();
-                    trace!("{:?}", persons);
-                    for (_first_name, _last_name, person_uuid) in persons {
-                        if umap.optout_contains(person_uuid) {
-                            warn!("Opt-out person uuid in research data!");
-                        } else {
-                            map.entry(person_uuid.to_string())
-                                .or_default()
-                                .push(uuid.clone());
-                        }
-                    }
-
-                    // Also other persons? These are present sometimes as "contributed to journal"
-                    // without uuids and other info.
-                    // Save these in a "backup_names" field?
-                    //let ex_persons = json.get_names();
-                    //println!("{:?}", ex_persons);
-
-                    // Add it to the data vector.
-                    let mut data = data.lock().unwrap();
-                    data.push(json);
-                }
-                Err(e) => {
-                    error!("{}", e);
-                    //error!("{}", line);
-
-                    // Increment the failure counter.
-                    let mut failed = failed_count.lock().unwrap();
-                    *failed += 1;
-                }
-            }
-        });
-
-    if *failed_count.lock().unwrap() > 0 {
-        warn!("Failed to parse {} lines.", *failed_count.lock().unwrap());
+    // We check for the "approved" workflowStep marker.
+    fn accept(&self) -> bool {
+        if !self.is_approved() {
+            debug!("Ignoring unapproved research item {:?}", self.uuid);
+        }
+        self.is_approved()
     }
+}
 
-    // person_research is a hash which looks like:
-    // {"862b1711-47e3-45ed-9330-a2071033c219": ["dd0ce568-96e7-449b-9a59-9ee857f79a13"],
-    //  "61781b1a-c069-4971-bb76-b18ed231a453": ["dd0ce568-96e7-449b-9a59-9ee857f79a13"]}
-    // Containing a person_id followed by [research_ids, ...]
-    let extracted_pr = Arc::try_unwrap(person_research)
-        .expect("Multiple references to person_research")
-        .into_inner()
-        .expect("Mutex was poisoned");
-    trace!("extracted_pr: {:?}", extracted_pr);
-
-    // Extract the data from Arc<Mutex<...>> and return it.
-    let extracted_data = Arc::try_unwrap(data).unwrap().into_inner().unwrap();
-    info!("Extracted {} entries.", extracted_data.len());
-    Ok((extracted_data, extracted_pr))
+pub fn read_research_jsonl(
+    file_path: &str,
+    rejects: &mut Rejects,
+) -> Result<Vec<ResearchJson>, LucrisError> {
+    read_all(JsonlReader::<ResearchJson>::open(file_path)?, rejects)
 }
 
 // ===========================================================================
@@ -1254,10 +1601,12 @@ mod tests {
     #[test]
     fn test_read_research_one() {
         let data_path = make_test_path("research_one.jsonl");
-        let umap = UuidMap::new();
         println!("{:?}", data_path);
-        let foo = read_research_jsonl(data_path.to_str().expect("Test data not found!"), &umap);
-        let (foo, _bar) = foo.unwrap();
+        let foo = read_research_jsonl(
+            data_path.to_str().expect("Test data not found!"),
+            &mut Rejects::default(),
+        );
+        let foo = foo.unwrap();
         let foo = &foo[0];
         assert_eq!(foo.get_uuid(), Some("1d136ffd-6d08-444a-9c50-76c0e5aec513"));
     }
@@ -1265,12 +1614,18 @@ mod tests {
     #[test]
     fn test_read_research_one_err() {
         let data_path = make_test_path("research_one_err.jsonl");
-        let umap = UuidMap::new();
         println!("{:?}", data_path);
-        let (foo, _bar) =
-            read_research_jsonl(data_path.to_str().expect("Test data not found!"), &umap)
-                .expect("Failed to read research JSONL data");
+        let mut rejects = Rejects::default();
+        let foo = read_research_jsonl(
+            data_path.to_str().expect("Test data not found!"),
+            &mut rejects,
+        )
+        .expect("Failed to read research JSONL data");
         assert_eq!(foo, []);
+        assert_eq!(rejects.len(), 1);
+        let reject = rejects.iter().next().unwrap();
+        assert_eq!(reject.line, Some(1));
+        assert_eq!(reject.stage, "parse");
     }
 
     // Tests the output of ResearchClean, and as a side-effect
@@ -1278,14 +1633,16 @@ mod tests {
     #[test]
     pub fn test_unknown_persons() {
         let data_path = make_test_path("journal.jsonl");
-        let umap = UuidMap::new();
         println!("{:?}", data_path);
-        let (foo, _bar) =
-            read_research_jsonl(data_path.to_str().expect("Test data not found!"), &umap)
-                .expect("Failed to read research JSONL data");
+        let foo = read_research_jsonl(
+            data_path.to_str().expect("Test data not found!"),
+            &mut Rejects::default(),
+        )
+        .expect("Failed to read research JSONL data");
         let mut umap = UuidMap::new();
         let research_des: ResearchClean =
-            ResearchClean::try_from_with_locale_umap(&foo[0], "en_GB", &mut umap).expect("Err");
+            ResearchClean::try_from_with_locale_umap(&foo[0], &"en_GB".into(), &mut umap)
+                .expect("Err");
         let output = format!("{}", research_des);
         assert_eq!(
             output,
@@ -1293,6 +1650,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_research_venue() {
+        let data_path = make_test_path("journal.jsonl");
+        let research = read_research_jsonl(
+            data_path.to_str().expect("Test data not found!"),
+            &mut Rejects::default(),
+        )
+        .expect("Failed to read research JSONL data");
+        let mut umap = UuidMap::new();
+        let rc = ResearchClean::try_from_with_locale_umap(&research[0], &"en_GB".into(), &mut umap)
+            .expect("Err");
+        assert_eq!(rc.venue.journal.as_deref(), Some("Biotechnology Letters"));
+        assert_eq!(rc.venue.issn.as_deref(), Some("1573-6776"));
+        assert_eq!(
+            rc.venue.to_string(),
+            "Biotechnology Letters 25(12): 927-933"
+        );
+        assert_eq!(rc.identifiers.dois, ["10.1023/a:1024032218981"]);
+        assert_eq!(
+            rc.identifiers.url().as_deref(),
+            Some("https://doi.org/10.1023/a:1024032218981")
+        );
+
+        let venue = Venue {
+            host_publication: Some("Proceedings of CLIN".to_string()),
+            publisher: Some("ACL".to_string()),
+            ..Venue::default()
+        };
+        assert_eq!(venue.to_string(), "Proceedings of CLIN, ACL");
+        assert_eq!(
+            normalise_isbn("978-91-7753-123-4").as_deref(),
+            Some("9789177531234")
+        );
+        assert_eq!(normalise_isbn("91-7753"), None);
+    }
+
     #[test]
     pub fn test_research_uuid() {
         let data = r#"{"pureId":2940508,"uuid":"1d136ffd-6d08-444a-9c50-76c0e5aec513"}"#;
@@ -1319,17 +1712,65 @@ mod tests {
         // Create and save the safe_uuid so we can compare it later.
         let safe_uuid = umap.add_uuid("01234567-0123-0123-0123-0123456789AB");
         let answer = format!(
-            r#"{{"uuid":"{}","title":"A nice title.","abstract":"","keywords":[],"persons":[],"creation_date":"","publication_date":""}}"#,
+            r#"{{"uuid":"{}","title":"A nice title.","abstract":null,"keywords":[],"persons":[],"creation_date":"","publication_date":"","research_type":"","peer_review":null,"language":"","concepts":[],"orgunits":[]}}"#,
             safe_uuid
         );
         let research: ResearchJson = serde_json::from_str(data).expect("Err");
         let research_des: ResearchClean =
-            ResearchClean::try_from_with_locale_umap(&research, "en_GB", &mut umap).expect("Err");
+            ResearchClean::try_from_with_locale_umap(&research, &"en_GB".into(), &mut umap)
+                .expect("Err");
         let research_des_jstr = serde_json::to_string(&research_des).unwrap();
         println!("{}", research_des_jstr);
         assert_eq!(research_des_jstr, answer);
     }
 
+    #[test]
+    fn test_research_locale_chain() {
+        let data = r#"
+        {
+          "uuid": "01234567-0123-0123-0123-0123456789AB",
+          "title": { "formatted": false, "value": "Tal och språk." },
+          "abstract": { "formatted": false, "text": [
+            { "locale": "sv_SE", "value": "Vi undersöker hur barn lär sig ljuden i sitt första språk." }
+          ] },
+          "keywordGroups": [ { "keywordContainers": [ { "freeKeywords": [
+            { "locale": "en_GB", "freeKeywords": ["speech"] },
+            { "locale": "sv_SE", "freeKeywords": ["tal"] }
+          ] } ] } ]
+        }
+        "#;
+        let research: ResearchJson = serde_json::from_str(data).expect("Err");
+        let mut umap = UuidMap::new();
+
+        // Only English, the Swedish abstract is left out.
+        let rc = ResearchClean::try_from_with_locale_umap(&research, &"en_GB".into(), &mut umap)
+            .expect("Err");
+        assert_eq!(rc.abstract_text, None);
+        assert_eq!(rc.keywords, ["speech"]);
+        assert_eq!(rc.locales.get("abstract"), None);
+        assert!(rc.translations.is_empty());
+
+        // English first, Swedish as fallback for the abstract.
+        let chain = LocaleChain::from("en_GB,sv_SE").bilingual(true);
+        let rc =
+            ResearchClean::try_from_with_locale_umap(&research, &chain, &mut umap).expect("Err");
+        assert!(rc.get_abstract().unwrap().starts_with("Vi undersöker"));
+        assert_eq!(rc.keywords, ["speech"]);
+        assert_eq!(rc.locales["abstract"], "sv_SE");
+        assert_eq!(rc.locales["keywords"], "en_GB");
+        assert_eq!(rc.translations["en_GB"].abstract_text, None);
+        assert_eq!(rc.translations["sv_SE"].abstract_text, rc.abstract_text);
+        assert_eq!(rc.translations["sv_SE"].keywords, ["tal"]);
+
+        // Detection, the title is too short.
+        let chain = LocaleChain::from("en_GB,sv_SE").match_language(true);
+        let rc =
+            ResearchClean::try_from_with_locale_umap(&research, &chain, &mut umap).expect("Err");
+        assert_eq!(rc.detected_languages.get("title"), None);
+        assert_eq!(rc.detected_languages["abstract"], "swe");
+        assert!(rc.language_mismatches.is_empty());
+    }
+
     #[test]
     fn test_research_clean_builder() {
         let rc = ResearchClean::builder("uuid-uuid-uuid")
@@ -1348,7 +1789,7 @@ mod tests {
         let repr = format!("{}", rc);
         assert_eq!(repr, "Test of a builder. [1/0/0]");
 
-        let repr_ok = r#"ResearchClean { uuid: "uuid-uuid-uuid", title: "Test of a builder.", abstract_text: "This shows how to use the builder pattern.", keywords: ["Rust Builder Pattern", "Example"], persons: [PersonRef { idx: 0, uuid: "uuid-uuid", name: "Quinten Berck", inex: Internal }], creation_date: "2026-08-12", publication_date: "2026" }"#;
+        let repr_ok = r#"ResearchClean { uuid: "uuid-uuid-uuid", title: "Test of a builder.", abstract_text: Some("This shows how to use the builder pattern."), keywords: ["Rust Builder Pattern", "Example"], persons: [PersonRef { idx: 0, uuid: "uuid-uuid", name: "Quinten Berck", inex: Internal }], creation_date: "2026-08-12", publication_date: "2026", research_type: "", peer_review: None, language: "", concepts: [], orgunits: [], locales: {}, translations: {}, detected_languages: {}, language_mismatches: [], venue: Venue { journal: None, issn: None, event: None, host_publication: None, publisher: None, volume: None, issue: None, pages: None, article_number: None }, identifiers: Identifiers { dois: [], isbns: [], external_id: None, links: [] }, duplicates: [], source_index: 0 }"#;
         let repr = format!("{:?}", rc);
         assert_eq!(repr, repr_ok);
     }
@@ -0,0 +1,277 @@
+use crate::errors::{parse_line, LucrisError, Rejects};
+use flate2::read::MultiGzDecoder;
+use log::{debug, info, warn};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde::de::DeserializeOwned;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Lines};
+
+// Streaming JSONL reader, used for all the input files. The lines are read
+// in chunks, each chunk is parsed in parallel, and the structs are returned
+// in the same order as the lines in the file. Only one chunk of parsed
+// structs is kept in memory, so the caller can convert (and drop) them one
+// by one.
+//
+// The input can be plain, gzip or zstd compressed (detected from the
+// first bytes, not the file name), or stdin ("-").
+//
+// Errors: a line which cannot be parsed is returned as a LucrisError::Parse
+// (with the line number), and goes to the Rejects. A read error (which
+// includes a broken compressed file) is returned as LucrisError::Io and
+// ends the file, it is never skipped, see reject().
+
+/// The number of lines parsed in parallel.
+pub const CHUNK_SIZE: usize = 1024;
+
+/// A progress line is logged every PROGRESS_LINES lines.
+pub const PROGRESS_LINES: usize = 100_000;
+
+/// One of the JSONL file types. accept() is called on every parsed
+/// record, records which are not accepted are skipped (not rejected).
+pub trait JsonlRecord: DeserializeOwned + Send {
+    /// The name used in the log messages.
+    const KIND: &'static str;
+
+    fn accept(&self) -> bool {
+        true
+    }
+}
+
+// A hook on a parsed record. Returns false to skip the record, can also
+// change it (for example to remove an opted-out person).
+type Hook<'a, T> = Box<dyn Fn(&mut T) -> bool + Send + Sync + 'a>;
+
+pub struct JsonlReader<'a, T> {
+    file_path: String,
+    lines: Lines<Box<dyn BufRead>>,
+    line_number: usize,
+    chunk_size: usize,
+    hooks: Vec<Hook<'a, T>>,
+    buffer: VecDeque<Result<T, LucrisError>>,
+    done: bool,
+}
+
+// Opens the file, or stdin for "-", and decompresses it if it starts with
+// the gzip or zstd magic bytes.
+fn open_input(file_path: &str) -> Result<Box<dyn BufRead>, io::Error> {
+    let mut reader: Box<dyn BufRead> = if file_path == "-" {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(BufReader::new(File::open(file_path)?))
+    };
+    let magic = reader.fill_buf()?;
+    if magic.starts_with(&[0x1f, 0x8b]) {
+        debug!("Reading {:?} as gzip.", file_path);
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
+    } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        debug!("Reading {:?} as zstd.", file_path);
+        Ok(Box::new(BufReader::new(
+            zstd::stream::read::Decoder::with_buffer(reader)?,
+        )))
+    } else {
+        Ok(reader)
+    }
+}
+
+// Parses one line, and applies accept() and the hooks.
+fn parse<T: JsonlRecord>(
+    file_path: &str,
+    hooks: &[Hook<T>],
+    line_number: usize,
+    line: &str,
+) -> Option<Result<T, LucrisError>> {
+    let mut record = match parse_line::<T>(file_path, line_number, line) {
+        Ok(record) => record,
+        Err(e) => return Some(Err(e)),
+    };
+    if !record.accept() {
+        debug!("Skipping {} on line {}.", T::KIND, line_number);
+        return None;
+    }
+    hooks
+        .iter()
+        .all(|hook| hook(&mut record))
+        .then_some(Ok(record))
+}
+
+impl<'a, T: JsonlRecord> JsonlReader<'a, T> {
+    pub fn open(file_path: &str) -> Result<Self, LucrisError> {
+        let reader = open_input(file_path).map_err(|e| LucrisError::io(file_path, e))?;
+        info!("Reading {} file {:?}.", T::KIND, file_path);
+        Ok(JsonlReader {
+            file_path: file_path.to_string(),
+            lines: reader.lines(),
+            line_number: 0,
+            chunk_size: CHUNK_SIZE,
+            hooks: vec![],
+            buffer: VecDeque::new(),
+            done: false,
+        })
+    }
+
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Adds a hook which is called (in parallel) on every accepted record,
+    /// in the order they were added.
+    pub fn with_hook(mut self, hook: impl Fn(&mut T) -> bool + Send + Sync + 'a) -> Self {
+        self.hooks.push(Box::new(hook));
+        self
+    }
+
+    /// Reads and parses the next chunk of lines, in file order. Returns an
+    /// empty Vec at the end of the file.
+    pub fn next_chunk(&mut self) -> Vec<Result<T, LucrisError>> {
+        let mut lines = Vec::with_capacity(self.chunk_size);
+        let mut read_error = None;
+        while !self.done && lines.len() < self.chunk_size {
+            match self.lines.next() {
+                Some(Ok(line)) => {
+                    self.line_number += 1;
+                    if self.line_number.is_multiple_of(PROGRESS_LINES) {
+                        info!("{}: {} lines read.", self.file_path, self.line_number);
+                    }
+                    lines.push((self.line_number, line));
+                }
+                Some(Err(e)) => {
+                    read_error = Some(LucrisError::io(&self.file_path, e));
+                    self.done = true;
+                }
+                None => {
+                    info!("{}: {} lines read.", self.file_path, self.line_number);
+                    self.done = true;
+                }
+            }
+        }
+
+        let (file_path, hooks) = (self.file_path.as_str(), self.hooks.as_slice());
+        let mut chunk: Vec<Result<T, LucrisError>> = lines
+            .into_par_iter()
+            .filter_map(|(line_number, line)| parse(file_path, hooks, line_number, &line))
+            .collect();
+        chunk.extend(read_error.map(Err));
+        chunk
+    }
+}
+
+impl<T: JsonlRecord> Iterator for JsonlReader<'_, T> {
+    type Item = Result<T, LucrisError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // A chunk can be empty when all its records are skipped.
+        while self.buffer.is_empty() && !self.done {
+            self.buffer = self.next_chunk().into();
+        }
+        self.buffer.pop_front()
+    }
+}
+
+/// Adds a parse error to the rejects, which stops in strict mode. Read
+/// errors are always returned.
+pub fn reject(error: LucrisError, rejects: &mut Rejects) -> Result<(), LucrisError> {
+    match error {
+        LucrisError::Io { .. } => Err(error),
+        _ => rejects.add(error),
+    }
+}
+
+/// Collects the records from a JsonlReader. The lines which cannot be
+/// parsed are added to the rejects.
+pub fn read_all<T>(
+    items: impl Iterator<Item = Result<T, LucrisError>>,
+    rejects: &mut Rejects,
+) -> Result<Vec<T>, LucrisError> {
+    let before = rejects.len();
+    let mut data = vec![];
+    for item in items {
+        match item {
+            Ok(json) => data.push(json),
+            Err(e) => reject(e, rejects)?,
+        }
+    }
+    if rejects.len() > before {
+        warn!("Failed to parse {} lines.", rejects.len() - before);
+    }
+    info!("Extracted {} entries.", data.len());
+    Ok(data)
+}
+
+// ===========================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use std::io::Write;
+
+    #[derive(Debug, Deserialize)]
+    struct Line {
+        n: usize,
+    }
+
+    impl JsonlRecord for Line {
+        const KIND: &'static str = "test";
+
+        fn accept(&self) -> bool {
+            self.n != 13
+        }
+    }
+
+    fn write_lines(output: &mut impl Write) {
+        for n in 0..100 {
+            if n == 42 {
+                writeln!(output, "{{\"n\": \"forty-two\"}}").unwrap();
+            } else {
+                writeln!(output, "{{\"n\": {}}}", n).unwrap();
+            }
+        }
+    }
+
+    fn read_numbers(path: &str) -> (Vec<usize>, usize) {
+        let mut rejects = Rejects::default();
+        let reader = JsonlReader::<Line>::open(path)
+            .expect("Failed to open file.")
+            .with_chunk_size(7)
+            .with_hook(|line| line.n % 10 != 0);
+        let data = read_all(reader, &mut rejects).expect("Lenient rejects should not fail.");
+        (data.iter().map(|line| line.n).collect(), rejects.len())
+    }
+
+    #[test]
+    fn test_order_rejects_and_hooks() {
+        let expected: Vec<usize> = (0..100)
+            .filter(|n| *n != 42 && *n != 13 && n % 10 != 0)
+            .collect();
+        let dir = std::env::temp_dir();
+        let id = uuid::Uuid::new_v4();
+
+        let path = dir.join(format!("lucris_test_jsonl_{id}.jsonl"));
+        let path = path.to_str().expect("Invalid path.");
+        write_lines(&mut File::create(path).expect("Failed to create file."));
+        assert_eq!(read_numbers(path), (expected.clone(), 1));
+
+        let gz_path = dir.join(format!("lucris_test_jsonl_{id}.jsonl.gz"));
+        let gz_path = gz_path.to_str().expect("Invalid path.");
+        let file = File::create(gz_path).expect("Failed to create file.");
+        let mut encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+        write_lines(&mut encoder);
+        encoder.finish().expect("Failed to compress.");
+        assert_eq!(read_numbers(gz_path), (expected.clone(), 1));
+
+        // The name does not matter, only the contents.
+        let zst_path = dir.join(format!("lucris_test_jsonl_{id}.data"));
+        let zst_path = zst_path.to_str().expect("Invalid path.");
+        let file = File::create(zst_path).expect("Failed to create file.");
+        let mut encoder = zstd::stream::write::Encoder::new(file, 0).expect("Failed to compress.");
+        write_lines(&mut encoder);
+        encoder.finish().expect("Failed to compress.");
+        assert_eq!(read_numbers(zst_path), (expected, 1));
+
+        for path in [path, gz_path, zst_path] {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
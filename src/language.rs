@@ -0,0 +1,111 @@
+use std::collections::BTreeMap;
+use whatlang::Lang;
+
+// Offline language identification (trigram based, see whatlang) of the
+// extracted texts. The locale tags in Pure are not always right, there is
+// English text under sv_SE and the reverse. The detected language is
+// tagged per field, and the fields where it differs from the declared
+// locale are flagged, see LanguageTags.
+//
+// The detected languages are ISO 639-3 codes ("eng", "swe").
+
+/// Texts shorter than this (in characters) are not checked, the detection
+/// is not reliable for them.
+pub const MIN_DETECT_CHARS: usize = 20;
+
+/// The minimum whatlang confidence. Its is_reliable() is too strict for
+/// titles and one-sentence texts.
+pub const MIN_CONFIDENCE: f64 = 0.5;
+
+/// The language of the text, None for short texts or when the detection
+/// is not confident enough.
+pub fn detect(text: &str) -> Option<Lang> {
+    if text.chars().count() < MIN_DETECT_CHARS {
+        return None;
+    }
+    whatlang::detect(text)
+        .filter(|info| info.confidence() >= MIN_CONFIDENCE)
+        .map(|info| info.lang())
+}
+
+/// The language of a Pure locale ("sv_SE" is Swedish), None for the
+/// locales we do not know.
+pub fn locale_language(locale: &str) -> Option<Lang> {
+    let code = locale.split(['_', '-']).next().unwrap_or("");
+    match code {
+        "en" => Some(Lang::Eng),
+        "sv" => Some(Lang::Swe),
+        "da" => Some(Lang::Dan),
+        "nb" | "no" => Some(Lang::Nob),
+        "fi" => Some(Lang::Fin),
+        "de" => Some(Lang::Deu),
+        "fr" => Some(Lang::Fra),
+        "es" => Some(Lang::Spa),
+        "it" => Some(Lang::Ita),
+        "nl" => Some(Lang::Nld),
+        "pt" => Some(Lang::Por),
+        _ => Lang::from_code(code),
+    }
+}
+
+/// Whether the text is (detected as) in the language of the locale.
+pub fn is_in_locale(text: &str, locale: &str) -> bool {
+    matches!((detect(text), locale_language(locale)), (Some(detected), Some(declared)) if detected == declared)
+}
+
+/// The detected language per field, and the fields where it differs from
+/// the declared locale.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LanguageTags {
+    pub detected: BTreeMap<String, String>,
+    pub mismatches: Vec<String>,
+}
+
+impl LanguageTags {
+    /// Detects the language of the text of a field. A field is a mismatch
+    /// when both the detected and the declared language are known and
+    /// differ.
+    pub fn check(&mut self, field: &str, text: &str, declared: Option<&str>) {
+        let Some(detected) = detect(text) else {
+            return;
+        };
+        self.detected
+            .insert(field.to_string(), detected.code().to_string());
+        if let Some(declared) = declared.and_then(locale_language) {
+            if declared != detected {
+                self.mismatches.push(field.to_string());
+            }
+        }
+    }
+}
+
+// ===========================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENGLISH: &str = "We study how children learn the sounds of their first language.";
+    const SWEDISH: &str = "Vi undersöker hur barn lär sig ljuden i sitt första språk.";
+
+    #[test]
+    fn test_detect_and_tags() {
+        assert_eq!(detect(ENGLISH), Some(Lang::Eng));
+        assert_eq!(detect(SWEDISH), Some(Lang::Swe));
+        assert_eq!(detect("Short text."), None);
+        assert_eq!(detect("[abstract missing]"), None);
+        assert_eq!(locale_language("sv_SE"), Some(Lang::Swe));
+        assert_eq!(locale_language("xx_XX"), None);
+        assert!(is_in_locale(ENGLISH, "en_GB"));
+        assert!(!is_in_locale(ENGLISH, "sv_SE"));
+
+        let mut tags = LanguageTags::default();
+        tags.check("title", ENGLISH, Some("en_GB"));
+        tags.check("abstract", ENGLISH, Some("sv_SE"));
+        tags.check("keywords", SWEDISH, None);
+        assert_eq!(tags.detected["title"], "eng");
+        assert_eq!(tags.detected["abstract"], "eng");
+        assert_eq!(tags.detected["keywords"], "swe");
+        assert_eq!(tags.mismatches, ["abstract"]);
+    }
+}
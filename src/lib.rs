@@ -0,0 +1,43 @@
+//! Reading, cleaning and combining the LUCRIS JSONL dumps.
+//!
+//! The `*Json` structs follow the data from the LUCRIS API, the `*Clean`
+//! structs contain only the fields we use, with "safe" uuids (see
+//! [`UuidMap`]). [`Combined`] connects the cleaned research and persons.
+//! The lucris-rs binary is a consumer of this crate.
+
+pub mod boilerplate;
+pub mod combined;
+pub mod concept_index;
+pub mod dedup;
+pub mod diff;
+pub mod errors;
+pub mod export;
+pub mod filter;
+pub mod filter_expr;
+pub mod filter_report;
+pub mod formatting;
+pub mod json_concepts;
+pub mod json_fingerprint;
+pub mod json_orgunits;
+pub mod json_person;
+pub mod json_research;
+pub mod jsonl;
+pub mod language;
+pub mod load;
+pub mod locale;
+pub mod names;
+pub mod optout;
+pub mod org_tree;
+pub mod redact;
+pub mod uuid_map;
+
+pub use combined::Combined;
+pub use filter::FilterMode;
+pub use json_person::{PersonClean, PersonJson};
+pub use json_research::{ResearchClean, ResearchJson};
+pub use uuid_map::UuidMap;
+
+// Runs the Rust examples in the README as doctests.
+#[cfg(doctest)]
+#[doc = include_str!("../README.md")]
+struct ReadmeDoctests;
@@ -0,0 +1,383 @@
+use crate::boilerplate::Boilerplate;
+use crate::concept_index::{ConceptIndex, ConceptOptions};
+use crate::errors::{CleanError, LucrisError, Rejects};
+use crate::json_concepts::read_concept_jsonl;
+use crate::json_fingerprint::read_fingerprint_jsonl;
+use crate::json_orgunits::read_orgunits_jsonl;
+use crate::json_person::{PersonClean, PersonJson};
+use crate::json_research::{ResearchClean, ResearchJson};
+use crate::jsonl::{reject, JsonlReader};
+use crate::locale::LocaleChain;
+use crate::optout::{OptOutPolicy, OptOutRule};
+use crate::org_tree::{OrgPath, OrgTree};
+use crate::redact::{known_values, PiiKind, Redactor};
+use crate::uuid_map::UuidMap;
+use log::{error, info, log_enabled, trace, warn, Level};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+// Reading and converting the JSONL files to the Clean structs, keyed on
+// the safe uuids, ready for Combined::new().
+
+/// The lookups done on the original uuids while converting, before they
+/// are replaced by safe uuids, and the opt-outs which are applied before
+/// converting. All can be empty.
+#[derive(Debug, Default)]
+pub struct Lookups {
+    pub concepts: ConceptIndex,
+    pub orgtree: OrgTree,
+    pub optout: OptOutPolicy,
+}
+
+impl Lookups {
+    // The orgunits (uuid, name from the record) as full paths.
+    fn org_paths(&self, orgunits: Vec<(&str, Option<&str>)>) -> Vec<OrgPath> {
+        orgunits
+            .into_iter()
+            .map(|(uuid, name)| self.orgtree.resolve(uuid, name))
+            .collect()
+    }
+}
+
+/// Reads a list of names (or keywords, terms) from a file, one per line.
+/// Empty lines are skipped.
+pub fn read_names(file_path: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let file = File::open(file_path)?;
+    let reader = BufReader::new(file);
+
+    let names = reader
+        .lines()
+        .map(|line| line.map(|name| name.trim().to_owned()))
+        .collect::<Result<Vec<String>, _>>()?
+        .into_iter()
+        .filter(|name| !name.is_empty())
+        .collect();
+
+    Ok(names)
+}
+
+/// Reads the fingerprints and concepts into a ConceptIndex. Without a
+/// fingerprints file the index is empty, without a concepts file there are
+/// no labels and all concepts are skipped.
+pub fn load_concept_index(
+    fingerprints_path: Option<&str>,
+    concepts_path: Option<&str>,
+    locale: &str,
+    options: ConceptOptions,
+    rejects: &mut Rejects,
+) -> Result<ConceptIndex, LucrisError> {
+    let Some(fingerprints_path) = fingerprints_path else {
+        return Ok(ConceptIndex::default());
+    };
+    let fingerprints = read_fingerprint_jsonl(fingerprints_path, rejects)?;
+    info!("Fingerprint data contains {} elements.", fingerprints.len());
+
+    let concepts = match concepts_path {
+        Some(concepts_path) => {
+            let concepts = read_concept_jsonl(concepts_path, rejects)?;
+            info!("Concepts data contains {} elements.", concepts.len());
+            concepts
+        }
+        None => {
+            warn!("Fingerprints without concepts, no concept labels available.");
+            vec![]
+        }
+    };
+    Ok(ConceptIndex::new(fingerprints, &concepts, locale, options))
+}
+
+/// Reads the orgunits into an OrgTree, empty without an orgunits file.
+/// The reference date (YYYY-MM-DD, today if None) decides which units
+/// and staff associations have ended.
+pub fn load_org_tree(
+    orgunits_path: Option<&str>,
+    locale: &str,
+    reference_date: Option<&str>,
+    rejects: &mut Rejects,
+) -> Result<OrgTree, LucrisError> {
+    let mut orgtree = match orgunits_path {
+        Some(orgunits_path) => {
+            let orgunits = read_orgunits_jsonl(orgunits_path, rejects)?;
+            info!("Orgunits data contains {} elements.", orgunits.len());
+            OrgTree::new(&orgunits, locale)
+        }
+        None => OrgTree::default(),
+    };
+    if let Some(date) = reference_date {
+        orgtree.set_reference_date(date);
+    }
+    Ok(orgtree)
+}
+
+// The persons file is read more than once, which is not possible for stdin.
+fn can_reread(file_path: &str) -> bool {
+    if file_path == "-" {
+        warn!("The persons are read from stdin, they are only read once.");
+    }
+    file_path != "-"
+}
+
+/// Reads the opt-out file into an OptOutPolicy, empty without a file. With
+/// a persons file, the names (and ORCIDs, emails) of the opted-out persons
+/// are added as well, so they are also found where they appear without uuid.
+pub fn load_optout_policy(
+    optout_path: Option<&str>,
+    persons_path: Option<&str>,
+    rule: OptOutRule,
+) -> Result<OptOutPolicy, LucrisError> {
+    let Some(optout_path) = optout_path else {
+        return Ok(OptOutPolicy::new(rule));
+    };
+    info!("Reading opt-out file {:?}.", optout_path);
+    let mut policy = OptOutPolicy::read(optout_path, rule)?;
+    if let Some(persons_path) = persons_path.filter(|path| can_reread(path)) {
+        // The persons file is read again (and checked) by load_persons(),
+        // the lines which cannot be parsed are skipped here.
+        let mut skipped = Rejects::default();
+        for person in JsonlReader::<PersonJson>::open(persons_path)? {
+            match person {
+                Ok(person) => policy.learn_person(&person),
+                Err(e) => reject(e, &mut skipped)?,
+            }
+        }
+        info!("Opt-out policy contains {} entries.", policy.len());
+    }
+    Ok(policy)
+}
+
+/// Creates the Redactor for the kinds. With a persons file, the emails,
+/// phone numbers and addresses in it are redacted as known values too.
+pub fn load_redactor(
+    kinds: &[PiiKind],
+    persons_path: Option<&str>,
+) -> Result<Redactor, Box<dyn std::error::Error>> {
+    let mut known = vec![];
+    if let Some(persons_path) = persons_path.filter(|path| !kinds.is_empty() && can_reread(path)) {
+        // The persons file is read again (and checked) by load_persons(),
+        // the lines which cannot be parsed are skipped here.
+        let mut skipped = Rejects::default();
+        for person in JsonlReader::<PersonJson>::open(persons_path)? {
+            match person {
+                Ok(person) => known.extend(known_values(&person)),
+                Err(e) => reject(e, &mut skipped)?,
+            }
+        }
+    }
+    info!("Redactor with {} known values.", known.len());
+    Ok(Redactor::new(kinds, &known)?)
+}
+
+/// Creates the Boilerplate detector, with the extra patterns (regular
+/// expressions, one per line, # for comments) from the file.
+pub fn load_boilerplate(
+    patterns_path: Option<&str>,
+    min_repeats: usize,
+) -> Result<Boilerplate, Box<dyn std::error::Error>> {
+    let mut patterns = vec![];
+    if let Some(patterns_path) = patterns_path {
+        let file = File::open(patterns_path).map_err(|e| LucrisError::io(patterns_path, e))?;
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| LucrisError::io(patterns_path, e))?;
+            let line = line.trim();
+            if !line.is_empty() && !line.starts_with('#') {
+                patterns.push(line.to_string());
+            }
+        }
+        info!("Read {} boilerplate patterns.", patterns.len());
+    }
+    Ok(Boilerplate::new(&patterns, min_repeats)?)
+}
+
+/// Parses the research data and converts it to ResearchClean. Returns a
+/// mapping from safe uuid to data, so we can combine later. The top
+/// concepts and org paths are taken from the Lookups. The file is
+/// streamed, each ResearchJson is dropped after it is converted, and the
+/// safe uuids are created in file order.
+pub fn load_research(
+    file_path: &str,
+    locale: &LocaleChain,
+    umap: &mut UuidMap,
+    lookups: &Lookups,
+    rejects: &mut Rejects,
+) -> Result<HashMap<String, ResearchClean>, LucrisError> {
+    let mut research_map: HashMap<String, ResearchClean> = HashMap::new();
+
+    // All the uuids are uniq (should be...).
+    info!("Convert ResearchJSON to ResearchClean.");
+    // The opt-outs are applied while parsing, see JsonlReader.
+    let reader = JsonlReader::<ResearchJson>::open(file_path)?
+        .with_hook(|entry| lookups.optout.apply_research(entry));
+    for (source_index, entry) in reader.enumerate() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                reject(e, rejects)?;
+                continue;
+            }
+        };
+        let entry = &entry;
+        if entry.get_uuid().is_some() {
+            // Convert the ResearchJson to ResearchClean, keeping only the
+            // relevant fields.
+            match ResearchClean::try_from_with_locale_umap(entry, locale, umap) {
+                Ok(mut research_des) => {
+                    if let Some(uuid) = entry.get_uuid() {
+                        research_des.concepts = lookups.concepts.top_concepts(uuid);
+                    }
+                    research_des.orgunits =
+                        lookups.org_paths(entry.get_orgunits_for_locale(locale.primary()));
+                    research_des.source_index = source_index;
+                    if log_enabled!(Level::Trace) {
+                        if let Ok(json_output) = serde_json::to_string_pretty(&research_des) {
+                            trace!("\n{}", json_output);
+                        }
+                    }
+                    research_map.insert(research_des.get_uuid().to_string(), research_des);
+                }
+                Err(e) => {
+                    rejects.add(LucrisError::Clean {
+                        file: file_path.to_string(),
+                        uuid: entry.get_uuid().map(String::from),
+                        error: e,
+                    })?;
+                }
+            }
+        } else {
+            rejects.add(LucrisError::Clean {
+                file: file_path.to_string(),
+                uuid: None,
+                error: CleanError::MissingUUID,
+            })?;
+        }
+    }
+
+    if locale.detects_language() {
+        let mismatches = research_map
+            .values()
+            .filter(|research| !research.language_mismatches.is_empty())
+            .count();
+        info!(
+            "Language differs from the locale in {} research outputs.",
+            mismatches
+        );
+    }
+
+    info!("Mappings {}.", umap);
+    for v in research_map.values() {
+        trace!("{}", v);
+    }
+    Ok(research_map)
+}
+
+/// Parses the persons JSON file, and converts to PersonClean. PersonClean
+/// is a simpler/cleaner version of PersonJson with only the fields we are
+/// interested in. Returns a mapping from safe uuid to data. Streamed like
+/// load_research().
+pub fn load_persons(
+    file_path: &str,
+    locale: &LocaleChain,
+    umap: &mut UuidMap,
+    lookups: &Lookups,
+    rejects: &mut Rejects,
+) -> Result<HashMap<String, PersonClean>, LucrisError> {
+    let mut person_map: HashMap<String, PersonClean> = HashMap::new();
+    let reader = JsonlReader::<PersonJson>::open(file_path)?
+        .with_hook(|entry| lookups.optout.apply_person(entry));
+    for entry in reader {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                reject(e, rejects)?;
+                continue;
+            }
+        };
+        let entry = &entry;
+        let Some(uuid) = entry.get_uuid() else {
+            rejects.add(LucrisError::Clean {
+                file: file_path.to_string(),
+                uuid: None,
+                error: CleanError::MissingUUID,
+            })?;
+            continue;
+        };
+        if let Some((first, last)) = entry.get_first_and_last_name() {
+            trace!("Name: {} {} {}", first, last, uuid);
+        } else {
+            error!("First or last name not found for {}", uuid);
+        }
+
+        // Convert to PersonClean structures.
+        match PersonClean::try_from_with_locale_umap(entry, locale, umap) {
+            Ok(mut person_des) => {
+                person_des.set_concepts(lookups.concepts.top_concepts(uuid));
+                person_des.set_orgunits(lookups.org_paths(
+                    entry.get_orgunits_for_locale(
+                        locale.primary(),
+                        lookups.orgtree.reference_date(),
+                    ),
+                ));
+                if log_enabled!(Level::Trace) {
+                    if let Ok(json_output) = serde_json::to_string(&person_des) {
+                        trace!("{}", json_output);
+                    }
+                }
+                person_map.insert(person_des.get_uuid().to_string(), person_des);
+            }
+            Err(e) => {
+                rejects.add(LucrisError::Clean {
+                    file: file_path.to_string(),
+                    uuid: Some(uuid.to_string()),
+                    error: e,
+                })?;
+            }
+        }
+    }
+
+    info!("Mappings {}.", umap);
+    for v in person_map.values() {
+        trace!("{}", v);
+    }
+    Ok(person_map)
+}
+
+// ===========================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_load_persons_rejects_missing_uuid() {
+        let path =
+            std::env::temp_dir().join(format!("lucris_test_persons_{}.jsonl", Uuid::new_v4()));
+        let path = path.to_str().expect("Invalid path.");
+        std::fs::write(
+            path,
+            concat!(
+                r#"{"uuid":"01234567-0123-0123-0123-0123456789ab","name":{"firstName":"Quinten","lastName":"Berck"}}"#,
+                "\n",
+                r#"{"name":{"firstName":"No","lastName":"Uuid"}}"#,
+                "\n"
+            ),
+        )
+        .unwrap();
+
+        let mut rejects = Rejects::default();
+        let persons = load_persons(
+            path,
+            &"en_GB".into(),
+            &mut UuidMap::new(),
+            &Lookups::default(),
+            &mut rejects,
+        )
+        .expect("Failed to load persons.");
+        assert_eq!(persons.len(), 1);
+        assert_eq!(rejects.len(), 1);
+        let reject = rejects.iter().next().unwrap();
+        assert_eq!(reject.stage, "clean");
+        assert_eq!(reject.uuid, None);
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,204 @@
+use crate::language::is_in_locale;
+use std::fmt;
+use std::str::FromStr;
+
+// The locales of the extracted texts. The LUCRIS texts are stored per
+// locale (mostly en_GB and sv_SE), and a record does not always have a
+// text in every locale. A LocaleChain is an ordered list of locales, for
+// each field the first locale in the chain with a text is used, and the
+// locale used is recorded in the cleaned record.
+//
+// In bilingual mode the texts in all the locales of the chain are kept as
+// well (in the "translations" of ResearchClean and PersonClean).
+//
+// The language of the texts can also be detected (see language.rs), and
+// used to pick the text in the right language when it is stored under
+// the wrong locale.
+
+/// An ordered list of locales, parsed from "en_GB,sv_SE".
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocaleChain {
+    locales: Vec<String>,
+    bilingual: bool,
+    detect_language: bool,
+    match_language: bool,
+}
+
+impl LocaleChain {
+    pub fn new<S: AsRef<str>>(locales: &[S]) -> Self {
+        let locales: Vec<String> = locales
+            .iter()
+            .map(|locale| locale.as_ref().trim())
+            .filter(|locale| !locale.is_empty())
+            .map(String::from)
+            .collect();
+        if locales.is_empty() {
+            return LocaleChain::default();
+        }
+        LocaleChain {
+            locales,
+            ..LocaleChain::default()
+        }
+    }
+
+    /// Keeps the texts in all the locales of the chain.
+    pub fn bilingual(mut self, bilingual: bool) -> Self {
+        self.bilingual = bilingual;
+        self
+    }
+
+    pub fn is_bilingual(&self) -> bool {
+        self.bilingual
+    }
+
+    /// Tags the detected language of the texts.
+    pub fn detect_language(mut self, detect_language: bool) -> Self {
+        self.detect_language = detect_language;
+        self
+    }
+
+    pub fn detects_language(&self) -> bool {
+        self.detect_language || self.match_language
+    }
+
+    /// Picks the texts on their detected language, see pick_text(). Also
+    /// tags the detected language.
+    pub fn match_language(mut self, match_language: bool) -> Self {
+        self.match_language = match_language;
+        self
+    }
+
+    /// The first locale, used where there is no fallback (the org unit
+    /// names, which fall back to English themselves).
+    pub fn primary(&self) -> &str {
+        &self.locales[0]
+    }
+
+    pub fn locales(&self) -> &[String] {
+        &self.locales
+    }
+
+    /// The first locale for which get() returns a value, with the value.
+    pub fn pick<T>(&self, get: impl Fn(&str) -> Option<T>) -> Option<(&str, T)> {
+        self.locales
+            .iter()
+            .find_map(|locale| get(locale).map(|value| (locale.as_str(), value)))
+    }
+
+    /// Like pick(), for the free texts. When matching on language, the
+    /// first locale of the chain for which a text is detected in its
+    /// language is used, the text can be stored under any of the locales
+    /// (its own first). Returns the locale the text is stored under.
+    pub fn pick_text<'a>(&self, get: impl Fn(&str) -> Option<&'a str>) -> Option<(&str, &'a str)> {
+        if self.match_language {
+            for wanted in &self.locales {
+                let stored =
+                    std::iter::once(wanted).chain(self.locales.iter().filter(|l| *l != wanted));
+                for locale in stored {
+                    if let Some(text) = get(locale).filter(|text| is_in_locale(text, wanted)) {
+                        return Some((locale.as_str(), text));
+                    }
+                }
+            }
+        }
+        self.pick(get)
+    }
+
+    /// Like pick(), for the getters which return an empty Vec when there
+    /// is nothing in a locale.
+    pub fn pick_vec<T>(&self, get: impl Fn(&str) -> Vec<T>) -> Option<(&str, Vec<T>)> {
+        self.pick(|locale| Some(get(locale)).filter(|values| !values.is_empty()))
+    }
+}
+
+impl Default for LocaleChain {
+    fn default() -> Self {
+        LocaleChain {
+            locales: vec!["en_GB".to_string()],
+            bilingual: false,
+            detect_language: false,
+            match_language: false,
+        }
+    }
+}
+
+impl FromStr for LocaleChain {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let locales: Vec<&str> = s.split(',').map(str::trim).collect();
+        if locales.iter().any(|locale| locale.is_empty()) {
+            return Err(format!("Invalid locale list {:?}.", s));
+        }
+        Ok(LocaleChain::new(&locales))
+    }
+}
+
+impl From<&str> for LocaleChain {
+    fn from(locale: &str) -> Self {
+        LocaleChain::new(&locale.split(',').collect::<Vec<_>>())
+    }
+}
+
+impl fmt::Display for LocaleChain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.locales.join(","))
+    }
+}
+
+// ===========================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_pick() {
+        let chain: LocaleChain = "en_GB, sv_SE".parse().expect("Valid list.");
+        assert_eq!(chain.locales(), ["en_GB", "sv_SE"]);
+        assert_eq!(chain.primary(), "en_GB");
+        assert_eq!(chain.to_string(), "en_GB,sv_SE");
+        assert!("en_GB,".parse::<LocaleChain>().is_err());
+
+        let texts = [("sv_SE", "Sammanfattning")];
+        let get = |locale: &str| {
+            texts
+                .iter()
+                .find(|(l, _)| *l == locale)
+                .map(|(_, text)| *text)
+        };
+        assert_eq!(chain.pick(get), Some(("sv_SE", "Sammanfattning")));
+        assert_eq!(LocaleChain::from("en_GB").pick(get), None);
+        assert_eq!(chain.pick_text(get), Some(("sv_SE", "Sammanfattning")));
+        assert_eq!(
+            chain.pick_vec(|locale| vec![locale.len(); usize::from(locale == "sv_SE")]),
+            Some(("sv_SE", vec![5]))
+        );
+    }
+
+    #[test]
+    fn test_match_language() {
+        // English text under sv_SE, Swedish under en_GB.
+        let texts = [
+            (
+                "en_GB",
+                "Vi undersöker hur barn lär sig ljuden i sitt första språk.",
+            ),
+            (
+                "sv_SE",
+                "We study how children learn the sounds of their first language.",
+            ),
+        ];
+        let get = |locale: &str| {
+            texts
+                .iter()
+                .find(|(l, _)| *l == locale)
+                .map(|(_, text)| *text)
+        };
+        let chain = LocaleChain::from("en_GB,sv_SE");
+        assert_eq!(chain.pick_text(get), Some(texts[0]));
+        let chain = chain.match_language(true);
+        assert!(chain.detects_language());
+        assert_eq!(chain.pick_text(get), Some(texts[1]));
+    }
+}
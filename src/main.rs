@@ -4,43 +4,58 @@
 // Output format...
 // ---------------------------------------------------------------------------
 //
-use clap::Parser;
-mod json_person;
-use json_person::{read_persons_jsonl, PersonClean, PersonJson};
-mod json_research;
-use json_research::{read_research_jsonl, ResearchClean, ResearchJson};
-mod json_fingerprint;
-use json_fingerprint::{read_fingerprint_jsonl, FingerprintJson};
-mod json_concepts;
-use json_concepts::{read_concept_jsonl, ConceptJson};
-mod json_orgunits;
-use json_orgunits::{read_orgunits_jsonl, OrgUnitJson};
-mod combined;
-use combined::Combined;
-mod formatting;
+use clap::{Args, Parser, Subcommand};
 use flexi_logger::{DeferredNow, Record};
 use flexi_logger::{Duplicate, FileSpec, LogSpecification, Logger, WriteMode};
-use formatting::{extract_text_with_formatting, extract_texts_with_formatting};
-use log::{debug, error, info, trace, LevelFilter};
+use log::{debug, error, info, warn, LevelFilter};
+use lucris_rs::concept_index::{ConceptIndex, ConceptOptions};
+use lucris_rs::dedup::{dedup_research, DedupOptions, DedupPolicy};
+use lucris_rs::diff::diff_jsonl;
+use lucris_rs::errors::{ErrorPolicy, LucrisError, Rejects};
+use lucris_rs::export::{
+    write_index_jsonl, write_persons_jsonl, write_persons_text, write_research_jsonl,
+    write_research_tsv, OutputOrder,
+};
+use lucris_rs::filter::{
+    filter_research_by_abstract, filter_research_by_date, filter_research_by_faculty,
+    filter_research_by_keyword, filter_research_by_language, filter_research_by_orgunit,
+    filter_research_by_peer_review, filter_research_by_person_fuzzy, filter_research_by_type,
+    parse_partial_date, FilterMode,
+};
+use lucris_rs::filter_expr::{filter_research_by_expr, FilterExpr};
+use lucris_rs::filter_report::FilterReport;
+use lucris_rs::json_concepts::read_concept_jsonl;
+use lucris_rs::json_fingerprint::read_fingerprint_jsonl;
+use lucris_rs::json_orgunits::read_orgunits_jsonl;
+use lucris_rs::load::{read_names, Lookups};
+use lucris_rs::locale::LocaleChain;
+use lucris_rs::names::NameMatcher;
+use lucris_rs::optout::{OptOutRule, VerifiedWriter};
+use lucris_rs::org_tree::OrgTree;
+use lucris_rs::redact::{redact_persons, redact_research, PiiKind};
+use lucris_rs::{Combined, PersonClean, ResearchClean, UuidMap};
 use std::collections::HashMap;
-use std::io::Write;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{BufReader, BufWriter, Write};
 use std::path::Path;
 use std::str::FromStr;
-mod errors;
-mod uuid_map;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
-use uuid_map::UuidMap;
-mod filter;
-use csv;
-use filter::{
-    filter_research_by_abstract, filter_research_by_keyword, filter_research_by_person, FilterMode,
-};
-use std::io;
 
 #[derive(Parser)]
 #[command(version, about, long_about = "Reading data.")]
 struct Cli {
+    #[command(subcommand)]
+    command: Commands,
+
+    /// Sets the level of logging;
+    /// error, warn, info, debug, or trace
+    #[arg(long = "ll", default_value = "warn", global = true)]
+    log_level: String,
+}
+
+/// The input files, shared by the subcommands which load the data.
+#[derive(Args)]
+struct InputArgs {
     /// Research info jsonl file
     #[arg(
         short,
@@ -69,14 +84,148 @@ struct Cli {
     )]
     orgunits: Option<String>,
 
-    /// Opt-out uuids.
+    /// Opt-out uuids and names.
     #[arg(
         short = 'u',
         long = "optout",
-        help = "The file containing the opt-out uuids."
+        help = "The file containing the opt-outs (uuids or names)."
     )]
     optout: Option<String>,
 
+    /// The date at which the orgunits and staff associations count as
+    /// ended.
+    #[arg(
+        long = "reference-date",
+        value_parser = parse_reference_date,
+        help = "The date (YYYY-MM-DD) at which orgunits and staff associations count as ended or not started. Defaults to today."
+    )]
+    reference_date: Option<String>,
+
+    /// What to do with research which has opted-out persons or orgunits.
+    #[arg(long = "optout-rule", value_enum, default_value_t = OptOutRule::RedactPerson)]
+    optout_rule: OptOutRule,
+
+    /// UUID mapping file.
+    #[arg(
+        short = 'm',
+        long = "uuidmap",
+        help = "The file containing the UUID mapping. It is read if it exists and saved at the end."
+    )]
+    uuidmap: Option<String>,
+
+    /// Salt for keyed safe UUIDs.
+    #[arg(
+        short = 's',
+        long = "salt",
+        help = "The file containing the secret salt used to derive stable safe UUIDs."
+    )]
+    salt: Option<String>,
+
+    /// Sets the locale for the extracted texts.
+    #[arg(
+        short,
+        long,
+        default_value = "en_GB",
+        help = "The locales of the extracted texts, in order of preference, for example en_GB,sv_SE."
+    )]
+    locale: LocaleChain,
+
+    /// Keep the texts in all the locales.
+    #[arg(
+        long,
+        help = "Keep the texts in all the --locale locales as translations, not only the first one found."
+    )]
+    bilingual: bool,
+
+    /// Detect the language of the texts.
+    #[arg(
+        long = "detect-language",
+        help = "Tag the detected language of the titles, abstracts and profile texts, and flag the ones which differ from their locale."
+    )]
+    detect_language: bool,
+
+    /// Pick the texts on their detected language.
+    #[arg(
+        long = "match-language",
+        help = "Pick the texts whose detected language matches the --locale locales, also when stored under another locale. Implies --detect-language."
+    )]
+    match_language: bool,
+
+    /// Number of fingerprint concepts per research output or person.
+    #[arg(long = "top-concepts", default_value_t = 10)]
+    top_concepts: usize,
+
+    /// Minimum weightedRank of a fingerprint concept.
+    #[arg(long = "min-concept-weight", default_value_t = 0.0)]
+    min_concept_weight: f64,
+
+    /// Stop at the first record which cannot be parsed or converted,
+    /// instead of skipping it.
+    #[arg(long)]
+    strict: bool,
+
+    /// Personal information to redact.
+    #[arg(
+        long = "redact",
+        value_enum,
+        value_delimiter = ',',
+        help = "Replace emails, phone numbers and/or addresses in the abstracts and profile texts by placeholders."
+    )]
+    redact: Vec<PiiKind>,
+
+    /// Redaction report.
+    #[arg(
+        long = "redaction-report",
+        help = "The file to write the number of redactions per record to (TSV)."
+    )]
+    redaction_report: Option<String>,
+
+    /// Extra boilerplate patterns.
+    #[arg(
+        long = "boilerplate",
+        help = "The file containing extra boilerplate patterns (regular expressions, one per line). Abstracts and profile texts which match are removed."
+    )]
+    boilerplate: Option<String>,
+
+    /// Texts repeated in this many records are boilerplate.
+    #[arg(
+        long = "boilerplate-repeats",
+        default_value_t = lucris_rs::boilerplate::DEFAULT_MIN_REPEATS,
+        help = "Remove the abstracts and profile texts found in at least this many records (0 to keep them)."
+    )]
+    boilerplate_repeats: usize,
+
+    /// Merge the duplicate research outputs.
+    #[arg(
+        long = "dedup",
+        value_enum,
+        help = "Merge the duplicate research outputs (same DOI, ISBN or external id, or similar title and authors), keeping one per the policy."
+    )]
+    dedup: Option<DedupPolicy>,
+
+    /// Minimum title similarity for duplicates.
+    #[arg(long = "dedup-threshold", default_value_t = lucris_rs::dedup::TITLE_THRESHOLD)]
+    dedup_threshold: f64,
+
+    /// Dedup report.
+    #[arg(
+        long = "dedup-report",
+        requires = "dedup",
+        help = "The file to write the duplicate clusters to (TSV)."
+    )]
+    dedup_report: Option<String>,
+
+    /// Rejects file.
+    #[arg(
+        long = "rejects",
+        help = "The file to write the skipped records to, one JSON object per line."
+    )]
+    rejects: Option<String>,
+}
+
+/// The research filters.
+#[derive(Args)]
+struct FilterArgs {
     #[arg(
         short = 'n',
         long = "names",
@@ -84,6 +233,22 @@ struct Cli {
     )]
     names: Option<String>,
 
+    /// Minimum similarity (0.0-1.0) for a name from the names file to match
+    /// a person. 1.0 only allows differences in diacritics and order, 0.95
+    /// also initials.
+    #[arg(long = "name-threshold", default_value_t = 1.0)]
+    name_threshold: f64,
+
+    /// The file to write the filter report to, which filter kept or
+    /// dropped each research output and why. TSV if the name ends in .tsv,
+    /// otherwise JSONL.
+    #[arg(long = "filter-report")]
+    filter_report: Option<String>,
+
+    /// The file to write the names which almost matched to (TSV).
+    #[arg(long = "near-misses")]
+    near_misses: Option<String>,
+
     #[arg(
         short = 'k',
         long = "keywords",
@@ -99,25 +264,185 @@ struct Cli {
     // abstract is a reserved word...
     abstractterms: Option<String>,
 
+    /// Keep research from this faculty (name), needs the orgunits file.
+    /// Can be repeated.
+    #[arg(long = "faculty")]
+    faculties: Vec<String>,
+
+    /// Keep research from this orgunit (uuid or name) and the units below
+    /// it. Can be repeated.
+    #[arg(long = "orgunit")]
+    orgunit_filter: Vec<String>,
+
+    /// Keep research published on or after this date (YYYY, YYYY-MM or
+    /// YYYY-MM-DD).
+    #[arg(long, value_parser = parse_partial_date)]
+    since: Option<String>,
+
+    /// Keep research published on or before this date (YYYY, YYYY-MM or
+    /// YYYY-MM-DD).
+    #[arg(long, value_parser = parse_partial_date)]
+    until: Option<String>,
+
+    /// Keep research of this type, e.g. "article", "thesis" or
+    /// "contributiontojournal/article". Can be repeated.
+    #[arg(long = "type")]
+    types: Vec<String>,
+
+    /// Keep peer-reviewed research.
+    #[arg(long = "peer-reviewed")]
+    peer_reviewed: bool,
+
+    /// Keep research in this language, e.g. "en" or "sv_SE". Can be
+    /// repeated.
+    #[arg(long = "language")]
+    languages: Vec<String>,
+
+    /// Keep research matching a filter expression, e.g.
+    /// 'person:"Peter Berck" AND (keyword:NLP OR abstract:~"language model")'.
+    #[arg(short = 'e', long = "expr")]
+    expression: Option<String>,
+
     #[arg(
         long = "filtermode",
         value_enum,
         default_value_t = FilterMode::KeepMatching
         )]
     filtermode: FilterMode,
+}
 
-    /// Sets the locale for the extracted texts.
-    #[arg(short, long, default_value = "en_GB")]
-    locale: String,
+#[derive(Subcommand)]
+enum Commands {
+    /// Export the (filtered) research as TSV or JSONL.
+    Export {
+        #[command(flatten)]
+        input: InputArgs,
+
+        #[command(flatten)]
+        filter: FilterArgs,
+
+        /// Output one JSON object per line instead of text.
+        #[arg(short = 'j', long)]
+        jsonl: bool,
+
+        /// The order of the research, ties are sorted on UUID.
+        #[arg(long, value_enum, default_value_t = OutputOrder::Date)]
+        order: OutputOrder,
+    },
+
+    /// Export one document per person, with all their (filtered) research.
+    Persons {
+        #[command(flatten)]
+        input: InputArgs,
+
+        #[command(flatten)]
+        filter: FilterArgs,
+
+        /// Output one JSON object per line instead of text.
+        #[arg(short = 'j', long)]
+        jsonl: bool,
+    },
+
+    /// Print statistics about the (filtered) data.
+    Stats {
+        #[command(flatten)]
+        input: InputArgs,
+
+        #[command(flatten)]
+        filter: FilterArgs,
+    },
+
+    /// Parse and convert the input files, and report the problems.
+    Validate {
+        #[command(flatten)]
+        input: InputArgs,
+    },
+
+    /// Look up research or persons by safe UUID.
+    Query {
+        #[command(flatten)]
+        input: InputArgs,
+
+        #[arg(long = "research-uuid", help = "Print the research and its persons.")]
+        research_uuid: Vec<String>,
+
+        #[arg(long = "person-uuid", help = "Print the research of the person.")]
+        person_uuid: Vec<String>,
+    },
+
+    /// Compare two JSONL exports on UUID.
+    Diff {
+        /// The old JSONL export.
+        old: String,
+
+        /// The new JSONL export.
+        new: String,
+    },
+
+    /// Output the person -> research index as JSONL.
+    Index {
+        #[command(flatten)]
+        input: InputArgs,
+
+        #[command(flatten)]
+        filter: FilterArgs,
+    },
+
+    /// Resolve safe UUIDs back to the original LUCRIS UUIDs.
+    Resolve {
+        #[arg(
+            short = 'm',
+            long = "uuidmap",
+            help = "The file containing the UUID mapping."
+        )]
+        uuidmap: String,
 
-    /// Sets the level of logging;
-    /// error, warn, info, debug, or trace
-    #[arg(long = "ll", default_value = "warn")]
-    log_level: String,
+        #[arg(
+            long = "audit",
+            default_value = "lucris-audit.log",
+            help = "The audit file, one line is appended per resolved UUID."
+        )]
+        audit: String,
+
+        /// The safe UUIDs to resolve.
+        #[arg(required = true)]
+        uuids: Vec<String>,
+    },
+
+    /// Check exported files for opted-out persons, research and orgunits.
+    Verify {
+        #[arg(
+            short = 'u',
+            long = "optout",
+            help = "The file containing the opt-outs (uuids or names)."
+        )]
+        optout: String,
 
-    /// Output one JSON object per line instead of text.
-    #[arg(short = 'j', long)]
-    jsonl: bool,
+        #[arg(
+            short,
+            long,
+            help = "The file containing the cleaned persons, to find the names, ORCIDs and emails of the opted-out persons."
+        )]
+        persons: Option<String>,
+
+        #[arg(
+            short = 'm',
+            long = "uuidmap",
+            help = "The file containing the UUID mapping, to find the safe UUIDs."
+        )]
+        uuidmap: Option<String>,
+
+        #[arg(
+            short = 's',
+            long = "salt",
+            help = "The file containing the secret salt, to derive the safe UUIDs."
+        )]
+        salt: Option<String>,
+
+        /// The exported files (TSV, JSONL or text).
+        #[arg(required = true)]
+        files: Vec<String>,
+    },
 }
 
 fn log_format(
@@ -142,533 +467,789 @@ fn log_format(
     )
 }
 
-// Helper function for names list.
-fn read_names(file_path: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-    let file = File::open(file_path)?;
-    let reader = BufReader::new(file);
-
-    let names = reader
-        .lines()
-        .map(|line| line.map(|name| name.trim().to_owned()))
-        .collect::<Result<Vec<String>, _>>()?
-        .into_iter()
-        .filter(|name| !name.is_empty())
-        .collect();
+// ----------------------------------------------------------------------------
+// Loading stage, shared by the subcommands.
+// ----------------------------------------------------------------------------
+
+// The map. This translates uuids to "safe" uuids. Existing mappings are
+// loaded from the uuidmap file so the safe uuids are the same between runs.
+fn load_uuidmap(input: &InputArgs) -> Result<UuidMap, Box<dyn std::error::Error>> {
+    let mut map = match input.uuidmap.as_deref() {
+        Some(filename) => {
+            info!("Reading uuidmap file {:?}", filename);
+            let map = UuidMap::load_or_new(filename)?;
+            info!("Read {} mappings.", map.count());
+            map
+        }
+        None => UuidMap::new(),
+    };
+    if let Some(filename) = input.salt.as_deref() {
+        info!("Reading salt file {:?}", filename);
+        map.read_salt(filename)?;
+    }
+    Ok(map)
+}
 
-    Ok(names)
+fn save_uuidmap(input: &InputArgs, umap: &UuidMap) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(filename) = input.uuidmap.as_deref() {
+        info!("Saving uuidmap file {:?}", filename);
+        umap.save(filename)?;
+    }
+    Ok(())
 }
 
-// TODO: Better error handling.
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let cli = Cli::parse();
+// Strict with --strict, lenient otherwise.
+fn error_policy(input: &InputArgs) -> ErrorPolicy {
+    if input.strict {
+        ErrorPolicy::Strict
+    } else {
+        ErrorPolicy::Lenient
+    }
+}
 
-    // This switches off logging from html5 and other crates.
-    let level_filter = LevelFilter::from_str(&cli.log_level).unwrap_or(LevelFilter::Off);
-    let log_spec = LogSpecification::builder()
-        .module("html5ever", LevelFilter::Off)
-        .module("lucris", level_filter) // Sets our level to the one on the cli.
-        .build();
+fn write_rejects(input: &InputArgs, rejects: &Rejects) -> Result<(), Box<dyn std::error::Error>> {
+    if !rejects.is_empty() {
+        warn!("Skipped {} records.", rejects.len());
+    }
+    if let Some(filename) = input.rejects.as_deref() {
+        info!("Writing rejects file {:?}", filename);
+        let file = File::create(filename)?;
+        rejects.write_jsonl(BufWriter::new(file))?;
+    }
+    Ok(())
+}
 
-    let _logger = Logger::with(log_spec)
-        .format(log_format)
-        .log_to_file(
-            FileSpec::default()
-                .suppress_timestamp()
-                .basename("lucris")
-                .suffix("log"),
-        )
-        .append()
-        .duplicate_to_stderr(Duplicate::All)
-        .write_mode(WriteMode::BufferAndFlush)
-        .start()?;
+// The concept labels and fingerprints, empty without a fingerprints file.
+fn load_concepts(input: &InputArgs, rejects: &mut Rejects) -> Result<ConceptIndex, LucrisError> {
+    let options = ConceptOptions {
+        top_n: input.top_concepts,
+        min_weight: input.min_concept_weight,
+    };
+    lucris_rs::load::load_concept_index(
+        input.fingerprints.as_deref(),
+        input.concepts.as_deref(),
+        input.locale.primary(),
+        options,
+        rejects,
+    )
+}
 
-    info!("Starting lucris-rs.");
+// The locale chain for the research and person texts.
+fn locales(input: &InputArgs) -> LocaleChain {
+    input
+        .locale
+        .clone()
+        .bilingual(input.bilingual)
+        .detect_language(input.detect_language)
+        .match_language(input.match_language)
+}
 
-    // ------------------------------------------------------------------------
-
-    // The map. This translates uuids to "safe" uuids.
-    // them somewhere.
-    let mut umap = {
-        let mut map = UuidMap::new();
-        if let Some(filename) = cli.optout.as_deref() {
-            info!("Reading optout file {:?}", filename);
-            let count = map.read_optouts(filename)?;
-            info!("Read {} optout UUIDs.", count);
-            info!("Mappings {}.", map);
+// The research data as ResearchClean, keyed on safe uuid.
+fn load_research(
+    input: &InputArgs,
+    umap: &mut UuidMap,
+    lookups: &Lookups,
+    rejects: &mut Rejects,
+) -> Result<HashMap<String, ResearchClean>, LucrisError> {
+    match input.research.as_deref() {
+        Some(research_filename) => lucris_rs::load::load_research(
+            research_filename,
+            &locales(input),
+            umap,
+            lookups,
+            rejects,
+        ),
+        None => {
+            debug!("No research data available.");
+            Ok(HashMap::new())
         }
-        map
-    };
+    }
+}
 
-    // Parse the research data, structures are pushed
-    // into a vector. Reads the research.jsonl and creates the
-    // person->[research, ...] vector.
-    let mut research_data: Option<Vec<ResearchJson>> = None;
-    let mut person_research: Option<HashMap<String, Vec<String>>> = None;
-
-    if let Some(research_filename) = cli.research {
-        info!("Reading research file {:?}.", research_filename);
-        match read_research_jsonl(&research_filename, &umap) {
-            Ok((res_data, pers_data)) => {
-                research_data = Some(res_data);
-                info!(
-                    "Research data contains {} elements.",
-                    research_data
-                        .as_ref() // Converts &Option<T> to Option<&T>.
-                        .expect("No research data")
-                        .len()
-                );
-                person_research = Some(pers_data);
-                info!(
-                    "Person-research contains {} elements.",
-                    person_research
-                        .as_ref()
-                        .expect("No person-research data")
-                        .len()
-                );
-            }
-            Err(e) => eprintln!("Error reading JSON: {}", e),
+// The persons as PersonClean, keyed on safe uuid.
+fn load_persons(
+    input: &InputArgs,
+    umap: &mut UuidMap,
+    lookups: &Lookups,
+    rejects: &mut Rejects,
+) -> Result<HashMap<String, PersonClean>, LucrisError> {
+    match input.persons.as_deref() {
+        Some(persons_filename) => {
+            lucris_rs::load::load_persons(persons_filename, &locales(input), umap, lookups, rejects)
+        }
+        None => {
+            debug!("No persons data available.");
+            Ok(HashMap::new())
         }
     }
+}
 
-    // Save a mapping from uuid to data, so we can combine later.
-    let mut research_map: HashMap<String, ResearchClean> = HashMap::new();
-
-    // All the uuids are uniq (should be...). We could make a map
-    // with uuids->data to connect it to the other data.
-    info!("Convert ResearchJSON to ResearchClean.");
-    if let Some(data) = research_data {
-        for entry in &data {
-            if let Some(uuid) = entry.get_uuid() {
-                //let comb = Combined::from(entry);
-                //println!("{:?}", comb);
-                /*
-                let person_names = entry.get_person_names(); // People responsible for the research.
-                for (i, (first_name, last_name, uuid)) in person_names.iter().enumerate() {
-                    trace!("Person {}: {} {} {}", i, first_name, last_name, uuid);
-                    // Often more than one.
-                    println!("PERSON{}: {} {} {}", i, first_name, last_name, uuid);
-                }
-                // Lookup uuid in person_data below. Connect. Does that give extra
-                // research info? Profile information?
-                //
-                // The abstract, cleaned because it often contains HTML.
-                let (abstract_title, abstract_text) = entry.get_title_abstract(&cli.locale);
-                let abstract_text = extract_text_with_formatting(abstract_text);
-                println!("TITLE: {}", abstract_title);
-                println!("ABSTRACT: {}", abstract_text);
-                */
-
-                // FILTER FORBIDDEN HERE? Done in person/research?
-
-                // Convert the ResearchJson to ResearchClean, keeping only the
-                // relevant fields.
-                match ResearchClean::try_from_with_locale_umap(entry, &cli.locale, &mut umap) {
-                    Ok(research_des) => {
-                        let json_output = serde_json::to_string_pretty(&research_des).unwrap();
-                        trace!("\n{}", json_output);
-                        let safe_uuid = umap.get_uuid_as_str(uuid);
-                        research_map.insert(safe_uuid, research_des);
-                    }
-                    Err(e) => {
-                        panic!("Failed to convert ResearchJson: {:?}", e);
-                    }
-                }
-            } else {
-                error!("Research JSON does not contain uuid.");
-            }
-        } // for entry
-
-    /*
-    let foo:Vec<ResearchJsonDes> = data.iter()
-        .map(|x| ResearchJsonDes::try_from_with_locale(x, &cli.locale).unwrap())
-        .collect();
-    */
-    } else {
-        debug!("No research data available.");
-    }
+// The orgunit hierarchy, empty without an orgunits file.
+fn load_org_tree(input: &InputArgs, rejects: &mut Rejects) -> Result<OrgTree, LucrisError> {
+    lucris_rs::load::load_org_tree(
+        input.orgunits.as_deref(),
+        input.locale.primary(),
+        input.reference_date.as_deref(),
+        rejects,
+    )
+}
 
-    info!("Mappings {}.", &umap);
+// A full YYYY-MM-DD date.
+fn parse_reference_date(date: &str) -> Result<String, String> {
+    parse_partial_date(date)
+        .ok()
+        .filter(|date| date.len() == 10)
+        .ok_or_else(|| format!("{date:?} is not YYYY-MM-DD"))
+}
 
-    for v in research_map.values() {
-        trace!("{}", v);
-    }
+// Reads all the input files and connects them in a Combined. Everything
+// in Combined is keyed on safe uuids.
+fn load_combined(
+    input: &InputArgs,
+    umap: &mut UuidMap,
+    rejects: &mut Rejects,
+) -> Result<Combined, LucrisError> {
+    let lookups = Lookups {
+        concepts: load_concepts(input, rejects)?,
+        orgtree: load_org_tree(input, rejects)?,
+        optout: lucris_rs::load::load_optout_policy(
+            input.optout.as_deref(),
+            input.persons.as_deref(),
+            input.optout_rule,
+        )?,
+    };
+    let research_map = load_research(input, umap, &lookups, rejects)?;
+    let person_map = load_persons(input, umap, &lookups, rejects)?;
 
-    // ------------------------------------------------------------------------
+    // The opt-outs are kept to verify the output.
+    info!("Creating Combined.");
+    let mut combined = Combined::new(research_map, person_map, lookups.optout);
+    combined.orgtree = lookups.orgtree;
+    info!("{}", combined);
+    Ok(combined)
+}
 
-    // Parse the persons JSON file. Each struct is pushed into
-    // a vector.
-    let persons_data: Option<Vec<PersonJson>> = cli.persons.as_ref().and_then(|persons_filename| {
-        info!("Reading persons file {:?}.", persons_filename);
-        match read_persons_jsonl(persons_filename, &umap) {
-            Ok(data) => {
-                info!("Person data contains {} elements.", data.len());
-                match serde_json::to_string_pretty(&data) {
-                    Ok(s) => trace!("\n{}", s),
-                    Err(e) => eprintln!("Cannot pretty print persons JSON: {:?}", e),
-                }
-                Some(data)
-            }
-            Err(e) => {
-                eprintln!("Failed to read PersonJson: {:?}", e);
-                None
-            }
-        }
-    });
-
-    // Save a mapping from uuid to data, so we can combine later. PersonClean
-    // is a simpler/cleaner version of PersonJson with only the fields we are
-    // interested in.
-    let person_map: HashMap<String, PersonClean> = persons_data
-        .as_deref()
-        .map(|data| {
-            data.iter()
-                .filter_map(|entry| {
-                    let uuid = entry.get_uuid()?;
-                    if let Some((first, last)) = entry.get_first_and_last_name() {
-                        trace!("Name: {} {} {}", first, last, uuid);
-                    } else {
-                        error!("First or last name not found for {}", uuid);
-                    }
-                    trace!("{:?}", entry.get_all_education_pure_ids());
-
-                    //let info_texts = entry.get_profile_information_texts_for_locale(&cli.locale);
-                    let info_texts = extract_texts_with_formatting(
-                        &entry.get_profile_information_texts_for_locale(&cli.locale),
-                    );
-                    trace!("{:?}", info_texts);
-
-                    // Convert to PersonClean structures.
-                    match PersonClean::try_from_with_locale_umap(entry, &cli.locale, &mut umap) {
-                        Ok(person_des) => {
-                            if let Ok(json_output) = serde_json::to_string(&person_des) {
-                                trace!("{}", json_output);
-                            }
-                            Some((uuid.to_string(), person_des))
-                        }
-                        Err(e) => {
-                            error!("Failed to convert PersonJson ({}): {:?}", uuid, e);
-                            None
-                        }
-                    }
-                })
-                .collect()
-        })
-        .unwrap_or_else(|| {
-            debug!("No persons data available.");
-            HashMap::new()
-        });
+// The loading stage: the uuidmap and the Combined data. The rejects file is
+// also written when loading stops in strict mode.
+fn load_all(input: &InputArgs) -> Result<(UuidMap, Combined), Box<dyn std::error::Error>> {
+    let mut umap = load_uuidmap(input)?;
+    let mut rejects = Rejects::new(error_policy(input));
+    let combined = load_combined(input, &mut umap, &mut rejects);
+    write_rejects(input, &rejects)?;
+    let mut combined = combined?;
+    strip_boilerplate(input, &mut combined)?;
+    if !input.redact.is_empty() {
+        redact(input, &mut combined)?;
+    }
+    if let Some(policy) = input.dedup {
+        dedup(input, policy, &mut combined)?;
+    }
+    Ok((umap, combined))
+}
 
-    info!("Mappings {}.", &umap);
+// Removes the placeholder and boilerplate abstracts and profile texts.
+fn strip_boilerplate(
+    input: &InputArgs,
+    combined: &mut Combined,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let boilerplate =
+        lucris_rs::load::load_boilerplate(input.boilerplate.as_deref(), input.boilerplate_repeats)?;
+    let research = boilerplate.strip_research(&mut combined.research);
+    let persons = boilerplate.strip_persons(&mut combined.persons);
+    info!(
+        "Removed {} boilerplate abstracts ({} repeated) and {} profile texts ({} repeated).",
+        research.total(),
+        research.repeated,
+        persons.total(),
+        persons.repeated
+    );
+    Ok(())
+}
 
-    for v in person_map.values() {
-        trace!("{}", v);
+// Replaces the personal information in the abstracts and profile texts,
+// and writes the counts per record to the redaction report.
+fn redact(input: &InputArgs, combined: &mut Combined) -> Result<(), Box<dyn std::error::Error>> {
+    let redactor = lucris_rs::load::load_redactor(&input.redact, input.persons.as_deref())?;
+    let mut entries = redact_research(&mut combined.research, &redactor);
+    entries.extend(redact_persons(&mut combined.persons, &redactor));
+    let total: usize = entries.iter().map(|entry| entry.counts.total()).sum();
+    info!("Redacted {} spans in {} records.", total, entries.len());
+    if let Some(filename) = input.redaction_report.as_deref() {
+        info!("Writing redaction report {:?}", filename);
+        lucris_rs::redact::write_tsv(&entries, BufWriter::new(File::create(filename)?))?;
     }
+    Ok(())
+}
 
-    /*for v in research_map.values() {
-        println!("research: {}", v);
-        for p in &v.persons {
-            let uuid = &p.uuid;
-            if let Some(value) = person_map.get(uuid) {
-                println!("-> person in research: {}", value);
-            }
-        }
-    }*/
-
-    // Go through the research_map, extracts the person-uuids and look them up in the
-    // person_map. Print/store/save/...
-
-    // ------------------------------------------------------------------------
-
-    // Parse the fingerprints JSON. Each struct is pushed into
-    // a vector.
-    let _fingerprints_data: Option<Vec<FingerprintJson>> =
-        cli.fingerprints.as_ref().and_then(|fingerprints_filename| {
-            info!("Reading fingerprint file {:?}.", fingerprints_filename);
-            match read_fingerprint_jsonl(fingerprints_filename) {
-                Ok(data) => {
-                    info!("Fingerprint data contains {} elements.", data.len());
-                    match ::serde_json::to_string_pretty(&data) {
-                        Ok(s) => trace!("\n{}", s),
-                        Err(e) => eprintln!("Cannot pretty print fingerprint JSON: {:?}", e),
-                    }
-                    for fp in &data {
-                        if let Some((id, uuids)) = fp.id_and_concepts() {
-                            //_uuids() {
-                            trace!("{id}: {:?}", uuids);
-                        }
-                    }
-                    Some(data)
-                }
-                Err(e) => {
-                    eprintln!("Error reading FingerprintJSON: {:?}", e);
-                    None
-                }
-            }
-        });
-
-    // ------------------------------------------------------------------------
-
-    // Parse the concepts JSON. Each struct is pushed into
-    // a vector.
-    let _concepts_data: Option<Vec<ConceptJson>> =
-        cli.concepts.as_ref().and_then(|concepts_filename| {
-            info!("Reading concepts file {:?}.", concepts_filename);
-            match read_concept_jsonl(concepts_filename) {
-                Ok(data) => {
-                    info!("Concepts data contains {} elements.", data.len());
-                    match ::serde_json::to_string_pretty(&data) {
-                        Ok(s) => trace!("\n{}", s),
-                        Err(e) => eprintln!("Cannot pretty print concept JSON: {:?}", e),
-                    }
-                    for c in &data {
-                        if let Some((id, text)) = c.id_and_text_for_locale(&cli.locale) {
-                            trace!("{id}: {text}");
-                        }
-                    }
-                    Some(data)
-                }
-                Err(e) => {
-                    eprintln!("Error reading ConceptJSON: {:?}", e);
-                    None
-                }
-            }
-        });
-    // ------------------------------------------------------------------------
-
-    // Parse the orgunits JSON. Each struct is pushed into
-    // a vector.
-    let _orgunits_data: Option<Vec<OrgUnitJson>> =
-        cli.orgunits.as_ref().and_then(|orgunits_filename| {
-            info!("Reading organisational-units file {:?}.", orgunits_filename);
-            match read_orgunits_jsonl(orgunits_filename) {
-                Ok(data) => {
-                    info!("Orgunits data contains {} elements.", data.len());
-                    match ::serde_json::to_string_pretty(&data) {
-                        Ok(s) => trace!("\n{}", s),
-                        Err(e) => eprintln!("Cannot pretty print orgunits JSON: {:?}", e),
-                    }
-                    Some(data)
-                }
-                Err(e) => {
-                    eprintln!("Error reading OrgunitsJSON: {:?}", e);
-                    None
-                }
-            }
-        });
-
-    // ------------------------------------------------------------------------
+// Merges the duplicate research outputs, and writes the clusters to the
+// dedup report.
+fn dedup(
+    input: &InputArgs,
+    policy: DedupPolicy,
+    combined: &mut Combined,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let options = DedupOptions {
+        policy,
+        title_threshold: input.dedup_threshold,
+        ..DedupOptions::default()
+    };
+    let clusters = dedup_research(combined, &options);
+    let merged: usize = clusters
+        .iter()
+        .map(|cluster| cluster.duplicates.len())
+        .sum();
+    info!(
+        "Merged {} duplicates in {} clusters.",
+        merged,
+        clusters.len()
+    );
+    if let Some(filename) = input.dedup_report.as_deref() {
+        info!("Writing dedup report {:?}", filename);
+        lucris_rs::dedup::write_tsv(&clusters, BufWriter::new(File::create(filename)?))?;
+    }
+    Ok(())
+}
 
-    // ------------------------------------------------------------------------
-    // Filter before we output.
-    // is this the right spot? Combined might contain a filtered list,
-    // while the other datastrcuts are unfiltered. FIXME
-    // ------------------------------------------------------------------------
+// Filter the research in Combined, and rebuild the person->research index.
+fn apply_filters(
+    combined: &mut Combined,
+    filter: &FilterArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let research_map = &mut combined.research;
+    let mut report = FilterReport::new(filter.filter_report.is_some());
 
-    // We need the Keep/Delete option as well. TODO
-    if let Some(names_filename) = cli.names {
+    if let Some(names_filename) = filter.names.as_deref() {
         info!("Before names file {} items.", research_map.len());
-        let names_list = read_names(&names_filename)?;
-        filter_research_by_person(&mut research_map, names_list, cli.filtermode);
+        let names_list = read_names(names_filename)?;
+        // Near misses are reported down to 0.1 below the threshold.
+        let matcher = NameMatcher::new(
+            &names_list,
+            filter.name_threshold,
+            filter.name_threshold - 0.1,
+        );
+        let near_misses = filter_research_by_person_fuzzy(
+            research_map,
+            &matcher,
+            &combined.persons,
+            filter.filtermode,
+            &mut report,
+        );
         info!("After names file {} items.", research_map.len());
+        for near_miss in &near_misses {
+            warn!(
+                "Name {:?} did not match, closest {:?} ({:.3}).",
+                near_miss.name, near_miss.candidate, near_miss.score
+            );
+        }
+        if let Some(filename) = filter.near_misses.as_deref() {
+            let mut writer = csv::WriterBuilder::new()
+                .delimiter(b'\t')
+                .from_path(filename)?;
+            writer.write_record(["name", "candidate", "score"])?;
+            for near_miss in &near_misses {
+                writer.write_record([
+                    near_miss.name.as_str(),
+                    near_miss.candidate.as_str(),
+                    &format!("{:.3}", near_miss.score),
+                ])?;
+            }
+            writer.flush()?;
+        }
     }
 
-    // We need the Keep/Delete option as well. TODO
     // FIXME this looks in keywords, not in abstracts!
-    if let Some(keywords_filename) = cli.keywords {
+    if let Some(keywords_filename) = filter.keywords.as_deref() {
         info!("Before keywords files {} items.", research_map.len());
-        let keywords_list = read_names(&keywords_filename)?;
-        filter_research_by_keyword(&mut research_map, keywords_list, cli.filtermode);
+        let keywords_list = read_names(keywords_filename)?;
+        filter_research_by_keyword(research_map, keywords_list, filter.filtermode, &mut report);
         info!("After keywords file {} items.", research_map.len());
     }
 
     // Abstracts filter.
-    if let Some(abstract_filename) = cli.abstractterms {
-        info!("Before keywords files {} items.", research_map.len());
-        let keywords_list = read_names(&abstract_filename)?;
-        let _ = filter_research_by_abstract(&mut research_map, keywords_list, cli.filtermode);
-        info!("After keywords file {} items.", research_map.len());
+    if let Some(abstract_filename) = filter.abstractterms.as_deref() {
+        info!("Before abstract file {} items.", research_map.len());
+        let terms_list = read_names(abstract_filename)?;
+        filter_research_by_abstract(research_map, terms_list, filter.filtermode, &mut report)?;
+        info!("After abstract file {} items.", research_map.len());
     }
 
-    // TODO: How to connect everything?
-    // Use Combined.
-    // Note that the person_reseach is the Option<...> returned from read_research_jsonl(...)
-    // without processing.
-    // If we don't read the research data, this will fail!
-    // Should this thing include "optout" uuids? We need to keep them somewhere.
-    // But the uuids have already been translated to "safe"... We can translate them too...
-    // umap is an arg to the functions, could be there too?
-    info!("Creating Combined.");
-    let optout_uuids = vec![];
-    let combined = Combined::new(
-        research_map,
-        person_map,
-        person_research.expect("No person_research data?"),
-        optout_uuids,
-    );
+    if !filter.faculties.is_empty() {
+        info!("Before faculty filter {} items.", research_map.len());
+        filter_research_by_faculty(research_map, filter.faculties.clone(), filter.filtermode);
+        info!("After faculty filter {} items.", research_map.len());
+    }
+
+    if !filter.orgunit_filter.is_empty() {
+        info!("Before orgunit filter {} items.", research_map.len());
+        filter_research_by_orgunit(
+            research_map,
+            filter.orgunit_filter.clone(),
+            &combined.orgtree,
+            filter.filtermode,
+        );
+        info!("After orgunit filter {} items.", research_map.len());
+    }
+
+    if filter.since.is_some() || filter.until.is_some() {
+        info!("Before date filter {} items.", research_map.len());
+        filter_research_by_date(
+            research_map,
+            filter.since.as_deref(),
+            filter.until.as_deref(),
+            filter.filtermode,
+        );
+        info!("After date filter {} items.", research_map.len());
+    }
+
+    if !filter.types.is_empty() {
+        info!("Before type filter {} items.", research_map.len());
+        filter_research_by_type(research_map, filter.types.clone(), filter.filtermode);
+        info!("After type filter {} items.", research_map.len());
+    }
+
+    if filter.peer_reviewed {
+        info!("Before peer-review filter {} items.", research_map.len());
+        filter_research_by_peer_review(research_map, filter.filtermode);
+        info!("After peer-review filter {} items.", research_map.len());
+    }
+
+    if !filter.languages.is_empty() {
+        info!("Before language filter {} items.", research_map.len());
+        filter_research_by_language(research_map, filter.languages.clone(), filter.filtermode);
+        info!("After language filter {} items.", research_map.len());
+    }
+
+    if let Some(expression) = filter.expression.as_deref() {
+        // Print the error with the position marked, the Debug output from
+        // main() is not readable.
+        let expr = FilterExpr::parse(expression, &combined.orgtree, filter.name_threshold)
+            .map_err(|e| {
+                eprintln!("{}", e);
+                "Invalid filter expression."
+            })?;
+        info!("Before filter expression {} items.", research_map.len());
+        filter_research_by_expr(research_map, &expr, &combined.persons, filter.filtermode);
+        info!("After filter expression {} items.", research_map.len());
+    }
+
+    if let Some(filename) = filter.filter_report.as_deref() {
+        info!(
+            "Writing {} filter decisions to {:?}.",
+            report.len(),
+            filename
+        );
+        let output = BufWriter::new(File::create(filename)?);
+        if filename.ends_with(".tsv") {
+            report.write_tsv(output)?;
+        } else {
+            report.write_jsonl(output)?;
+        }
+    }
+
+    combined.reindex();
     info!("{}", combined);
-    //trace!("{:?}", &combined);
-
-    // dd0ce568-96e7-449b-9a59-9ee857f79a13 (ok in research_1.jsonl)
-    // 147e206b-b9d5-49a6-bc83-ddec9ff21af1 (ok in research_10.jasonl)
-    // dd0ce568-96e7-449b-9a59-9ee857f79a13 (err in research_10.jasonl)
-    //
-    /*
-    match combined.get_research_from_uuid_ref("dd0ce568-96e7-449b-9a59-9ee857f79a13") {
-        Ok((research, persons)) => {
-            println!("Research: {:?}", research);
-            for person in persons {
-                println!("{} / {}", research, person);
+    Ok(())
+}
+
+// Writes the output to stdout, checking each line for opt-outs before it
+// is written. At the first leak the output stops, and the leaks are
+// printed to stderr.
+fn write_verified<F>(
+    combined: &Combined,
+    umap: &UuidMap,
+    write: F,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    F: FnOnce(&mut dyn Write) -> Result<(), Box<dyn std::error::Error>>,
+{
+    let stdout = BufWriter::new(io::stdout().lock());
+    let mut output = VerifiedWriter::new(stdout, &combined.optout, umap);
+    let result = write(&mut output).and_then(|()| Ok(output.finish()?));
+    let leaks = output.leaks();
+    if !leaks.is_empty() {
+        for leak in leaks {
+            eprintln!("{}\t{}\t{}", leak.line, leak.kind, leak.identifier);
+        }
+        return Err(format!("Found {} opt-outs in the output.", leaks.len()).into());
+    }
+    result
+}
+
+// ----------------------------------------------------------------------------
+// Subcommands.
+// ----------------------------------------------------------------------------
+
+// Print the output to stdout, TSV (default) or JSONL.
+fn export(
+    input: &InputArgs,
+    filter: &FilterArgs,
+    jsonl: bool,
+    order: OutputOrder,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (umap, mut combined) = load_all(input)?;
+    apply_filters(&mut combined, filter)?;
+
+    write_verified(&combined, &umap, |output| {
+        if jsonl {
+            write_research_jsonl(&combined, order, output)
+        } else if !combined.research.is_empty() {
+            write_research_tsv(&combined, order, output)
+        } else {
+            Ok(())
+        }
+    })?;
+
+    save_uuidmap(input, &umap)
+}
+
+// Print the person documents to stdout, text (default) or JSONL.
+fn persons(
+    input: &InputArgs,
+    filter: &FilterArgs,
+    jsonl: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (umap, mut combined) = load_all(input)?;
+    apply_filters(&mut combined, filter)?;
+
+    write_verified(&combined, &umap, |output| {
+        if jsonl {
+            write_persons_jsonl(&combined, output)
+        } else {
+            write_persons_text(&combined, output)
+        }
+    })?;
+
+    save_uuidmap(input, &umap)
+}
+
+fn stats(input: &InputArgs, filter: &FilterArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let (umap, mut combined) = load_all(input)?;
+    apply_filters(&mut combined, filter)?;
+
+    let (mut internal, mut external, mut unknown) = (0, 0, 0);
+    for r in combined.research.values() {
+        for p in &r.persons {
+            match (p.is_internal(), p.is_external()) {
+                (true, _) => internal += 1,
+                (_, true) => external += 1,
+                _ => unknown += 1,
             }
         }
-        Err(e) => eprintln!("Error: {:?}", e),
     }
-    */
+    let no_abstract = combined
+        .research
+        .values()
+        .filter(|r| r.get_abstract().is_none_or(|text| text.trim().is_empty()))
+        .count();
+    let no_research = combined
+        .persons
+        .keys()
+        .filter(|uuid| !combined.person_research.contains_key(*uuid))
+        .count();
+
+    println!("research\t{}", combined.research.len());
+    println!("research_without_abstract\t{}", no_abstract);
+    println!("persons\t{}", combined.persons.len());
+    println!("persons_without_research\t{}", no_research);
+    println!("person_research\t{}", combined.person_research.len());
+    println!("internal_person_refs\t{}", internal);
+    println!("external_person_refs\t{}", external);
+    println!("unknown_person_refs\t{}", unknown);
+    println!("uuids\t{}", umap.count());
+
+    save_uuidmap(input, &umap)
+}
 
-    //combined.output_test();
+// Reads and converts each file on its own, so the files can be checked
+// without the others. Returns an error if something could not be converted.
+fn validate(input: &InputArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let mut umap = load_uuidmap(input)?;
+    let mut rejects = Rejects::new(error_policy(input));
+    let result = validate_files(input, &mut umap, &mut rejects);
+    write_rejects(input, &rejects)?;
+    result?;
+
+    if !rejects.is_empty() {
+        return Err(format!("{} records could not be read or converted.", rejects.len()).into());
+    }
+    Ok(())
+}
 
-    /*
-    println!("combined.get_research_for_person_uuid(...)");
-    match combined.get_research_for_person_uuid("61781b1a-c069-4971-bb76-b18ed231a453") {
-        Ok(res) => {
-            for r in res {
-                println!("-> {}", r);
+fn validate_files(
+    input: &InputArgs,
+    umap: &mut UuidMap,
+    rejects: &mut Rejects,
+) -> Result<(), LucrisError> {
+    if let Some(filename) = input.research.as_deref() {
+        let before = rejects.len();
+        let data = load_research(input, umap, &Lookups::default(), rejects)?;
+        println!(
+            "{}\tconverted {}\trejected {}",
+            filename,
+            data.len(),
+            rejects.len() - before
+        );
+    }
+    if let Some(filename) = input.persons.as_deref() {
+        let before = rejects.len();
+        let data = load_persons(input, umap, &Lookups::default(), rejects)?;
+        println!(
+            "{}\tconverted {}\trejected {}",
+            filename,
+            data.len(),
+            rejects.len() - before
+        );
+    }
+    if let Some(filename) = input.fingerprints.as_deref() {
+        let before = rejects.len();
+        let data = read_fingerprint_jsonl(filename, rejects)?;
+        println!(
+            "{}\tread {}\trejected {}",
+            filename,
+            data.len(),
+            rejects.len() - before
+        );
+    }
+    if let Some(filename) = input.concepts.as_deref() {
+        let before = rejects.len();
+        let data = read_concept_jsonl(filename, rejects)?;
+        println!(
+            "{}\tread {}\trejected {}",
+            filename,
+            data.len(),
+            rejects.len() - before
+        );
+    }
+    if let Some(filename) = input.orgunits.as_deref() {
+        let before = rejects.len();
+        let data = read_orgunits_jsonl(filename, rejects)?;
+        println!(
+            "{}\tread {}\trejected {}",
+            filename,
+            data.len(),
+            rejects.len() - before
+        );
+    }
+    Ok(())
+}
+
+// Prints the research (JSONL) with the internal persons, or the research
+// of a person.
+fn query(
+    input: &InputArgs,
+    research_uuids: &[String],
+    person_uuids: &[String],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (umap, combined) = load_all(input)?;
+
+    write_verified(&combined, &umap, |output| {
+        for uuid in research_uuids {
+            match combined.get_research_from_uuid_ref(uuid) {
+                Ok((research, persons)) => {
+                    serde_json::to_writer(&mut *output, research)?;
+                    writeln!(output)?;
+                    for person in persons {
+                        serde_json::to_writer(&mut *output, person)?;
+                        writeln!(output)?;
+                    }
+                }
+                Err(e) => error!("Research {}: {:?}", uuid, e),
             }
-        },
-        _ => ()
-    }
-    */
-
-    // Output name, research title & abstract (nta format for haystack_research.py).
-    // What we want is maybe a HayStack compatible (JSON) string.
-    //   doc = Document(content=page.content, meta={"title": page.title, "url": page.url})
-    // for (person_uuid, person) in &combined.persons {
-    //     //println!("\n{}", person.get_name());
-    //     if let Ok(res) = combined.get_research_for_person_uuid(person_uuid) {
-    //         for r in res {
-    //             /*println!("{}, {}, {}",
-    //             person.get_name(),
-    //             r.get_title(),
-    //             r.get_abstract()
-    //             );*/
-    //             // TODO there is a get_internal_person_names() too.
-    //             // Better to iterate over research instead of persons.
-    //             println!(
-    //                 "NAME:{}\nTITLE:{}\nABSTRACT:{}",
-    //                 person.get_name(),
-    //                 r.get_title(),
-    //                 r.get_abstract()
-    //             );
-    //         }
-    //     }
-    // }
-
-    // Print the output to stdout. Simple format,
-    // NAME: ... (multiple names)
-    // TITLE: ...
-    // KEYWORDS: ...
-    // CREATED: ...
-    // PUBLISHED: ...
-    // ABSTRACT: ...
-    if !cli.jsonl {
-        // We really need a CSV output mode as well...
-        /*
-        for r in combined.research.values() {
-            debug!("research clean uuid={:?}", r.get_uuid());
-            trace!("{:?}", r);
-            // If empty abstract skip?
-            let names: Vec<_> = r
-                .persons
-                .iter()
-                //.filter(|p| p.is_internal()) // Filter by the `inex` variable
-                .map(|p| p.get_name())
-                .collect();
-            if names.is_empty() {
-                eprintln!("No names! {}", r.get_title());
-            } else {
-                // TODO Check the type of research (journal, etc).
-                /*
-                println!("NAMES:{}", names.join(","));
-                println!("TITLE:{}", r.get_title());
-                println!("KEYWORDS:{}", r.get_keywords().join(","));
-                println!("CREATED:{}", r.get_creation_date());
-                println!("PUBLISHED:{}", r.get_publication_date());
-                let s = r.get_abstract();
-                /*
-                s.split_whitespace()
-                    .collect::<Vec<&str>>()
-                    .join(" ");
-                */
-                //println!("ABSTRACT:{}", r.get_abstract());
-                println!("ABSTRACT:{}", s);
-                */
-                print!("\"{}\"\t", names.join(","));
-                print!("\"{}\"\t", r.get_title());
-                print!("\"{}\"\t", r.get_keywords().join(","));
-                print!("\"{}\"\t", r.get_publication_date());
-                let s = r.get_abstract();
-                println!("\"{}\"", escape(&s));
+        }
+
+        for uuid in person_uuids {
+            match combined.get_research_for_person_uuid(uuid) {
+                Ok(research) => {
+                    for r in research {
+                        serde_json::to_writer(&mut *output, r)?;
+                        writeln!(output)?;
+                    }
+                }
+                Err(e) => error!("Person {}: {:?}", uuid, e),
             }
         }
-        */
-    } else {
-        let stdout = std::io::stdout();
-        let mut output = stdout.lock();
+        Ok(())
+    })?;
 
-        let mut research: Vec<&ResearchClean> = combined.research.values().collect();
-        research.sort_by_key(|item| item.get_publication_date()); // Even get_keywords() works here.
+    save_uuidmap(input, &umap)
+}
 
-        for item in research {
-            serde_json::to_writer(&mut output, item)?;
-            writeln!(output)?;
-        }
+fn diff(old: &str, new: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let diff = diff_jsonl(old, new)?;
+    for uuid in &diff.removed {
+        println!("-\t{}", uuid);
+    }
+    for uuid in &diff.added {
+        println!("+\t{}", uuid);
     }
+    for uuid in &diff.changed {
+        println!("~\t{}", uuid);
+    }
+    info!(
+        "Removed {}, added {}, changed {}, unchanged {}.",
+        diff.removed.len(),
+        diff.added.len(),
+        diff.changed.len(),
+        diff.unchanged
+    );
+    Ok(())
+}
 
-    if (cli.jsonl == false) && (combined.research.len() > 0) {
-        let stdout = io::stdout();
-        let mut output = stdout.lock();
-        // UTF-8 BOM
-        output.write_all(b"\xEF\xBB\xBF")?;
-        let mut writer = csv::WriterBuilder::new()
-            .delimiter(b'\t')
-            .from_writer(output);
-        writer.write_record(["names", "title", "keywords", "pubdate", "abstract"])?;
-
-        for r in combined.research.values() {
-            let names: Vec<_> = r
-                .persons
-                .iter()
-                //.filter(|p| p.is_internal()) // Filter by the `inex` variable
-                .map(|p| p.get_name())
-                .collect();
-            let keywords = r.get_keywords().join(",");
-            let abstract_text = r
-                .get_abstract()
-                .split_whitespace()
-                .collect::<Vec<_>>()
-                .join(" ");
-            if abstract_text.len() > 0 {
-                // skip empty abstract
-                let abstract_text = extract_text_with_formatting(&abstract_text);
-                writer.write_record([
-                    names.join(","),
-                    r.get_title().to_string(),
-                    keywords.to_string(),
-                    r.get_publication_date().to_string(),
-                    abstract_text,
-                ])?;
+fn index(input: &InputArgs, filter: &FilterArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let (umap, mut combined) = load_all(input)?;
+    apply_filters(&mut combined, filter)?;
+
+    write_verified(&combined, &umap, |output| {
+        write_index_jsonl(&combined, output)
+    })?;
+
+    save_uuidmap(input, &umap)
+}
+
+// Prints the original uuid for each safe uuid. Every lookup is written to
+// the audit file, this is the only place where original uuids are output.
+fn resolve(uuidmap: &str, audit: &str, uuids: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    info!("Reading uuidmap file {:?}", uuidmap);
+    let umap = UuidMap::load(uuidmap)?;
+    let mut audit_file = OpenOptions::new().create(true).append(true).open(audit)?;
+    let user = std::env::var("USER").unwrap_or_else(|_| "unknown".to_string());
+
+    for safe_uuid in uuids {
+        match umap.resolve_audited(safe_uuid, &user, &mut audit_file)? {
+            Some(uuid) => println!("{}\t{}", safe_uuid, uuid),
+            None => {
+                error!("Safe uuid {} not found in {}.", safe_uuid, uuidmap);
+                println!("{}\t-", safe_uuid);
             }
         }
+    }
+    Ok(())
+}
 
-        writer.flush()?;
+// Scans the exported files for the opt-outs. Prints one line per hit
+// (file, line number, kind, identifier) and returns an error if there
+// were any.
+fn verify(
+    optout: &str,
+    persons: Option<&str>,
+    uuidmap: Option<&str>,
+    salt: Option<&str>,
+    files: &[String],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let policy = lucris_rs::load::load_optout_policy(Some(optout), persons, OptOutRule::default())?;
+    if persons.is_none() {
+        warn!("No persons file, only the names in the opt-out file are checked.");
+    }
+    let mut umap = match uuidmap {
+        Some(filename) => UuidMap::load(filename)?,
+        None => UuidMap::new(),
+    };
+    if let Some(filename) = salt {
+        umap.read_salt(filename)?;
     }
 
-    // ------------------------------------------------------------------------
+    let mut count = 0;
+    for filename in files {
+        info!("Verifying {:?}.", filename);
+        let reader = BufReader::new(File::open(filename)?);
+        let leaks = policy.verify(reader, &umap)?;
+        for leak in &leaks {
+            println!(
+                "{}\t{}\t{}\t{}",
+                filename, leak.line, leak.kind, leak.identifier
+            );
+        }
+        count += leaks.len();
+    }
+
+    if count > 0 {
+        return Err(format!("Found {} opt-outs in the output.", count).into());
+    }
+    info!("No opt-outs found in {} files.", files.len());
+    Ok(())
+}
 
-    /*
-    let id = Uuid::new_v4();
-    println!("{} {}", id, id.urn());
-    */
+// TODO: Better error handling.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+
+    // This switches off logging from html5 and other crates.
+    let level_filter = LevelFilter::from_str(&cli.log_level).unwrap_or(LevelFilter::Off);
+    let log_spec = LogSpecification::builder()
+        .module("html5ever", LevelFilter::Off)
+        .module("lucris", level_filter) // Sets our level to the one on the cli.
+        .build();
+
+    let _logger = Logger::with(log_spec)
+        .format(log_format)
+        .log_to_file(
+            FileSpec::default()
+                .suppress_timestamp()
+                .basename("lucris")
+                .suffix("log"),
+        )
+        .append()
+        .duplicate_to_stderr(Duplicate::All)
+        .write_mode(WriteMode::BufferAndFlush)
+        .start()?;
+
+    info!("Starting lucris-rs.");
+
+    match &cli.command {
+        Commands::Export {
+            input,
+            filter,
+            jsonl,
+            order,
+        } => export(input, filter, *jsonl, *order)?,
+        Commands::Persons {
+            input,
+            filter,
+            jsonl,
+        } => persons(input, filter, *jsonl)?,
+        Commands::Stats { input, filter } => stats(input, filter)?,
+        Commands::Validate { input } => validate(input)?,
+        Commands::Query {
+            input,
+            research_uuid,
+            person_uuid,
+        } => query(input, research_uuid, person_uuid)?,
+        Commands::Diff { old, new } => diff(old, new)?,
+        Commands::Index { input, filter } => index(input, filter)?,
+        Commands::Resolve {
+            uuidmap,
+            audit,
+            uuids,
+        } => resolve(uuidmap, audit, uuids)?,
+        Commands::Verify {
+            optout,
+            persons,
+            uuidmap,
+            salt,
+            files,
+        } => verify(
+            optout,
+            persons.as_deref(),
+            uuidmap.as_deref(),
+            salt.as_deref(),
+            files,
+        )?,
+    }
 
     info!("Ending lucris-rs.");
     Ok(())
 }
+
+// ===========================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    // Catches clashing argument names between the flattened Args.
+    #[test]
+    fn test_cli() {
+        Cli::command().debug_assert();
+    }
+}
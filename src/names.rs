@@ -0,0 +1,244 @@
+use log::debug;
+use std::collections::HashSet;
+use strsim::jaro_winkler;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+// Person-name normalisation and matching for the names filter. The names
+// lists come from the AI Lund member workbook, where names can be UTF-8
+// decoded as MacRoman ("Bj√∂rk"), use initials, lack the Swedish
+// diacritics, or are written as "Last, First".
+
+// The characters 0x80-0xFF in MacRoman, used to undo the wrong decoding
+// (like text.encode("mac_roman").decode("utf-8") in read_ai_lund_members.py).
+const MAC_ROMAN: &str = concat!(
+    "ÄÅÇÉÑÖÜáàâäãåçéèêëíìîïñóòôöõúùûü",
+    "†°¢£§•¶ß®©™´¨≠ÆØ∞±≤≥¥µ∂∑∏π∫ªºΩæø",
+    "¿¡¬√ƒ≈∆«»…\u{a0}ÀÃÕŒœ–—“”‘’÷◊ÿŸ⁄€‹›ﬁﬂ",
+    "‡·‚„‰ÂÊÁËÈÍÎÏÌÓÔ\u{f8ff}ÒÚÛÙıˆ˜¯˘˙˚¸˝˛ˇ",
+);
+
+/// Repairs UTF-8 text which was decoded as MacRoman. Returns the text
+/// unchanged if it cannot be repaired.
+pub fn repair_mac_roman(text: &str) -> String {
+    let mut bytes = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        if c.is_ascii() {
+            bytes.push(c as u8);
+        } else if let Some(idx) = MAC_ROMAN.chars().position(|m| m == c) {
+            bytes.push(0x80 + idx as u8);
+        } else {
+            return text.to_string();
+        }
+    }
+    String::from_utf8(bytes).unwrap_or_else(|_| text.to_string())
+}
+
+/// Normalises a name for comparing: repairs the encoding, puts "Last,
+/// First" in "First Last" order, removes the diacritics and punctuation,
+/// and lowercases. "Björk, Åsa-Karin" becomes "asa karin bjork".
+pub fn normalize_name(name: &str) -> String {
+    let name = repair_mac_roman(name.trim());
+    let name = match name.split_once(',') {
+        Some((last, first)) if !first.trim().is_empty() => format!("{} {}", first, last),
+        _ => name,
+    };
+    fold_text(&name)
+}
+
+/// Removes the diacritics and punctuation from a text, and lowercases it.
+/// The words are separated by single spaces.
+pub fn fold_text(text: &str) -> String {
+    text.nfkd()
+        .filter(|c| !is_combining_mark(*c))
+        .map(|c| match c {
+            // These do not decompose.
+            'ø' | 'Ø' => 'o',
+            'æ' | 'Æ' => 'a',
+            'ł' | 'Ł' => 'l',
+            'ß' => 's',
+            c if c.is_alphanumeric() => c,
+            _ => ' ',
+        })
+        .collect::<String>()
+        .to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The similarity of names which only match on initials ("p berck" and
+/// "peter berck"). Below 1.0, because an initial can stand for more than
+/// one colleague with the same last name.
+pub const INITIALS_SCORE: f64 = 0.95;
+
+// "p berck" and "peter berck": the same last name, and the other parts
+// the same or an initial.
+fn initials_match(a: &str, b: &str) -> bool {
+    let a: Vec<&str> = a.split(' ').collect();
+    let b: Vec<&str> = b.split(' ').collect();
+    if a.len() != b.len() || a.len() < 2 || a.last() != b.last() {
+        return false;
+    }
+    a.iter().zip(&b).all(|(x, y)| {
+        x == y
+            || (x.chars().count() == 1 && y.starts_with(*x))
+            || (y.chars().count() == 1 && x.starts_with(*y))
+    })
+}
+
+/// How similar two normalised names are, between 0.0 and 1.0. Initials
+/// score at least INITIALS_SCORE. The names are also compared with the
+/// parts sorted, for "Last First" without a comma.
+pub fn name_similarity(a: &str, b: &str) -> f64 {
+    if a == b {
+        return 1.0;
+    }
+    let sorted = |name: &str| {
+        let mut parts: Vec<&str> = name.split(' ').collect();
+        parts.sort();
+        parts.join(" ")
+    };
+    let score = jaro_winkler(a, b).max(jaro_winkler(&sorted(a), &sorted(b)));
+    if initials_match(a, b) {
+        return score.max(INITIALS_SCORE);
+    }
+    score
+}
+
+/// A name from the list which did not match, but came close.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NearMiss {
+    pub name: String,
+    pub candidate: String,
+    pub score: f64,
+}
+
+/// Matches the names from a names list against the person names in the
+/// research. `threshold` is the minimum similarity for a match (1.0 is an
+/// exact match after normalisation, INITIALS_SCORE allows initials),
+/// candidates between `near_miss` and `threshold` are reported.
+#[derive(Debug)]
+pub struct NameMatcher {
+    names: Vec<(String, String)>,
+    threshold: f64,
+    near_miss: f64,
+}
+
+impl NameMatcher {
+    pub fn new(names: &[String], threshold: f64, near_miss: f64) -> Self {
+        let names = names
+            .iter()
+            .map(|name| (name.trim().to_string(), normalize_name(name)))
+            .filter(|(_, normalized)| !normalized.is_empty())
+            .collect();
+        NameMatcher {
+            names,
+            threshold,
+            near_miss,
+        }
+    }
+
+    /// The best (name from the list, similarity) for a person name.
+    pub fn best_match(&self, candidate: &str) -> Option<(&str, f64)> {
+        let candidate = normalize_name(candidate);
+        self.names
+            .iter()
+            .map(|(name, normalized)| (name.as_str(), name_similarity(normalized, &candidate)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    pub fn matches(&self, candidate: &str) -> bool {
+        self.best_match(candidate)
+            .is_some_and(|(_, score)| score >= self.threshold)
+    }
+
+    /// The names from the list which matched none of the candidates, with
+    /// their closest candidate if that one is a near miss.
+    pub fn near_misses<'a>(&self, candidates: impl IntoIterator<Item = &'a str>) -> Vec<NearMiss> {
+        let candidates: HashSet<String> = candidates.into_iter().map(String::from).collect();
+        let mut near_misses = vec![];
+        for (name, normalized) in &self.names {
+            let best = candidates
+                .iter()
+                .map(|c| (c, name_similarity(normalized, &normalize_name(c))))
+                .max_by(|a, b| a.1.total_cmp(&b.1).then_with(|| b.0.cmp(a.0)));
+            match best {
+                Some((_, score)) if score >= self.threshold => {}
+                Some((candidate, score)) if score >= self.near_miss => {
+                    near_misses.push(NearMiss {
+                        name: name.clone(),
+                        candidate: candidate.clone(),
+                        score,
+                    });
+                }
+                _ => debug!("No match for {:?}.", name),
+            }
+        }
+        near_misses
+    }
+}
+
+// ===========================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_name() {
+        assert_eq!(repair_mac_roman("Bj√∂rk √Ösa"), "Björk Åsa");
+        assert_eq!(repair_mac_roman("Åsa Björk"), "Åsa Björk");
+        assert_eq!(normalize_name("Björk, Åsa-Karin"), "asa karin bjork");
+        assert_eq!(normalize_name("√Ösa Bj√∂rk"), "asa bjork");
+        assert_eq!(normalize_name("  Søren  Ærø "), "soren aro");
+    }
+
+    #[test]
+    fn test_name_similarity() {
+        assert_eq!(name_similarity("p berck", "peter berck"), INITIALS_SCORE);
+        assert_eq!(name_similarity("peter berck", "berck peter"), 1.0);
+        assert!(name_similarity("p berck", "p borck") < 1.0);
+        assert!(name_similarity("peter berck", "petter berck") > 0.9);
+    }
+
+    #[test]
+    fn test_name_matcher() {
+        let names = vec![
+            "Berck, Peter".to_string(),
+            "Asa Bjork".to_string(),
+            "Petra Berk".to_string(),
+            "Nobody Atall".to_string(),
+        ];
+        let matcher = NameMatcher::new(&names, 1.0, 0.8);
+        assert!(matcher.matches("Peter Berck"));
+        assert!(matcher.matches("Åsa Björk"));
+        assert!(!matcher.matches("Petra Berck"));
+
+        let near_misses = matcher.near_misses(["Peter Berck", "Åsa Björk", "Petra Berck"]);
+        assert_eq!(near_misses.len(), 1);
+        assert_eq!(near_misses[0].name, "Petra Berk");
+        assert_eq!(near_misses[0].candidate, "Petra Berck");
+
+        let matcher = NameMatcher::new(&names, 0.95, 0.8);
+        assert!(matcher.matches("Petra Berck"));
+    }
+
+    #[test]
+    fn test_initials_are_near_misses() {
+        // Two colleagues with the same last name and initial.
+        let names = vec!["P. Berck".to_string()];
+        let matcher = NameMatcher::new(&names, 1.0, 0.9);
+        assert!(!matcher.matches("Peter Berck"));
+        assert!(!matcher.matches("Paula Berck"));
+        let near_misses = matcher.near_misses(["Peter Berck", "Paula Berck"]);
+        assert_eq!(near_misses.len(), 1);
+        assert_eq!(near_misses[0].name, "P. Berck");
+        assert_eq!(near_misses[0].score, INITIALS_SCORE);
+
+        let matcher = NameMatcher::new(&names, INITIALS_SCORE, 0.9);
+        assert!(matcher.matches("Peter Berck"));
+        assert!(matcher.matches("Paula Berck"));
+        assert!(!matcher.matches("Paula Borck"));
+    }
+}
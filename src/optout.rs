@@ -0,0 +1,677 @@
+use crate::errors::LucrisError;
+use crate::json_person::PersonJson;
+use crate::json_research::{PersonAssociation, ResearchJson};
+use crate::names::{fold_text, normalize_name};
+use crate::uuid_map::UuidMap;
+use clap::ValueEnum;
+use log::info;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use uuid::Uuid;
+
+// All the opt-out handling. The opt-out file has one entry per line:
+//
+//   6ed28bcf-7127-4ad9-8120-96eecf60f57c            a person uuid
+//   person:6ed28bcf-7127-4ad9-8120-96eecf60f57c     the same
+//   research:<uuid>                                 a research output
+//   orgunit:<uuid>                                  an organisational unit
+//   name:Åsa Björk                                  a name
+//   orcid:0000-0002-1825-0097                       an ORCID
+//   email:asa.bjork@example.org                     an email address
+//
+// Empty lines and lines starting with # are skipped, any other line which
+// cannot be understood is an error. The uuids are the
+// original LUCRIS uuids. The policy is applied to the Json structs before
+// they are converted, so opted-out persons never get a safe uuid and their
+// names never reach the Clean structs. verify() checks the output
+// afterwards.
+
+/// What to do with a research output which has an opted-out person (or
+/// orgunit). Opted-out research outputs are always dropped.
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum OptOutRule {
+    /// Drop the whole research output.
+    #[value(name = "drop")]
+    DropResearch,
+    /// Remove the person (or orgunit) and keep the research output.
+    #[default]
+    #[value(name = "redact")]
+    RedactPerson,
+}
+
+#[derive(Debug, Default)]
+pub struct OptOutPolicy {
+    persons: HashSet<String>,
+    research: HashSet<String>,
+    orgunits: HashSet<String>,
+    // Normalised, see normalize_name().
+    names: HashSet<String>,
+    // Lowercase, only used by verify().
+    orcids: HashSet<String>,
+    emails: HashSet<String>,
+    rule: OptOutRule,
+}
+
+/// An opted-out identifier or name found in an output, see verify().
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Leak {
+    pub line: usize,
+    // "person", "research", "orgunit", "safe uuid", "orcid", "email" or
+    // "name".
+    pub kind: &'static str,
+    pub identifier: String,
+}
+
+// The name of a person in a research output, internal or external.
+fn association_name(association: &PersonAssociation) -> Option<String> {
+    if let Some(name) = association.name.as_ref() {
+        let parts: Vec<&str> = [&name.firstName, &name.lastName]
+            .into_iter()
+            .filter_map(|part| part.as_deref())
+            .collect();
+        if !parts.is_empty() {
+            return Some(parts.join(" "));
+        }
+    }
+    association
+        .externalPerson
+        .as_ref()?
+        .name
+        .as_ref()?
+        .text
+        .iter()
+        .find_map(|lv| lv.value.clone())
+}
+
+// The uuids are kept the way LUCRIS writes them, lowercase with hyphens,
+// so an uppercase, braced or unhyphenated uuid in the opt-out file still
+// matches.
+fn normalize_uuid(uuid: &str) -> String {
+    let uuid = uuid.trim();
+    Uuid::parse_str(uuid)
+        .map(|uuid| uuid.hyphenated().to_string())
+        .unwrap_or_else(|_| uuid.to_lowercase())
+}
+
+impl OptOutPolicy {
+    pub fn new(rule: OptOutRule) -> Self {
+        OptOutPolicy {
+            rule,
+            ..Default::default()
+        }
+    }
+
+    /// Reads the opt-out file. A line which cannot be understood is an
+    /// error, skipping it would export the data of someone who opted out.
+    pub fn read(file_path: &str, rule: OptOutRule) -> Result<Self, LucrisError> {
+        let file = File::open(file_path).map_err(|e| LucrisError::io(file_path, e))?;
+        let mut policy = Self::new(rule);
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|e| LucrisError::io(file_path, e))?;
+            policy
+                .add_line(&line)
+                .map_err(|message| LucrisError::Invalid {
+                    file: file_path.to_string(),
+                    line: index + 1,
+                    message: format!("{} in opt-out line {:?}", message, line.trim()),
+                })?;
+        }
+        info!("Read {} opt-outs.", policy.len());
+        Ok(policy)
+    }
+
+    /// Adds one line from an opt-out file.
+    pub fn add_line(&mut self, line: &str) -> Result<(), String> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(());
+        }
+        let (kind, value) = match line.split_once(':') {
+            Some((kind, value)) => (kind.trim().to_lowercase(), value.trim()),
+            None => ("person".to_string(), line),
+        };
+        let is_uuid = Uuid::parse_str(value).is_ok();
+        match kind.as_str() {
+            "person" | "research" | "orgunit" if !is_uuid => return Err("invalid uuid".to_string()),
+            "person" => self.add_person(value),
+            "research" => self.add_research(value),
+            "orgunit" => self.add_orgunit(value),
+            "name" => self.add_name(value),
+            "orcid" => self.add_orcid(value),
+            "email" => self.add_email(value),
+            _ => return Err(format!("unknown kind {:?}", kind)),
+        }
+        Ok(())
+    }
+
+    pub fn add_person(&mut self, uuid: &str) {
+        self.persons.insert(normalize_uuid(uuid));
+    }
+
+    pub fn add_research(&mut self, uuid: &str) {
+        self.research.insert(normalize_uuid(uuid));
+    }
+
+    pub fn add_orgunit(&mut self, uuid: &str) {
+        self.orgunits.insert(normalize_uuid(uuid));
+    }
+
+    pub fn add_name(&mut self, name: &str) {
+        let name = normalize_name(name);
+        if !name.is_empty() {
+            self.names.insert(name);
+        }
+    }
+
+    pub fn add_orcid(&mut self, orcid: &str) {
+        let orcid = orcid.rsplit('/').next().unwrap_or(orcid).trim();
+        if !orcid.is_empty() {
+            self.orcids.insert(orcid.to_lowercase());
+        }
+    }
+
+    pub fn add_email(&mut self, email: &str) {
+        let email = email.trim();
+        if !email.is_empty() {
+            self.emails.insert(email.to_lowercase());
+        }
+    }
+
+    /// Adds the names, name variants, ORCIDs and email addresses of the
+    /// person if it opted out, so they are also found where there is no
+    /// uuid.
+    pub fn learn_person(&mut self, person: &PersonJson) {
+        if !person
+            .get_uuid()
+            .is_some_and(|uuid| self.persons.contains(&normalize_uuid(uuid)))
+        {
+            return;
+        }
+        if let Some((first, last)) = person.get_first_and_last_name() {
+            self.add_name(&format!("{} {}", first, last));
+        }
+        for variant in person.get_name_variants() {
+            self.add_name(&variant);
+        }
+        if let Some(orcid) = person.get_orcid() {
+            self.add_orcid(orcid);
+        }
+        for email in person.get_emails() {
+            self.add_email(email);
+        }
+    }
+
+    pub fn rule(&self) -> OptOutRule {
+        self.rule
+    }
+
+    pub fn len(&self) -> usize {
+        self.persons.len()
+            + self.research.len()
+            + self.orgunits.len()
+            + self.names.len()
+            + self.orcids.len()
+            + self.emails.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_person_opted_out(&self, uuid: Option<&str>, name: Option<&str>) -> bool {
+        uuid.is_some_and(|uuid| self.persons.contains(&normalize_uuid(uuid)))
+            || name.is_some_and(|name| self.names.contains(&normalize_name(name)))
+    }
+
+    fn is_association_opted_out(&self, association: &PersonAssociation) -> bool {
+        let uuid = association
+            .person
+            .as_ref()
+            .and_then(|p| p.uuid.as_deref())
+            .or_else(|| association.externalPerson.as_ref()?.uuid.as_deref());
+        self.is_person_opted_out(uuid, association_name(association).as_deref())
+    }
+
+    /// Applies the policy to a research output. Returns false if it has to
+    /// be dropped, otherwise the opted-out persons and orgunits are removed
+    /// (with the redact rule).
+    pub fn apply_research(&self, research: &mut ResearchJson) -> bool {
+        let uuid = research.get_uuid().unwrap_or("").to_string();
+        if self.research.contains(&normalize_uuid(&uuid)) {
+            info!("Opt-out research {}.", uuid);
+            return false;
+        }
+
+        let opted_out_persons = research
+            .personAssociations
+            .as_deref()
+            .unwrap_or(&[])
+            .iter()
+            .filter(|a| self.is_association_opted_out(a))
+            .count();
+        let opted_out_unit = |unit: &crate::json_research::OrganisationalUnit| {
+            unit.uuid
+                .as_deref()
+                .is_some_and(|uuid| self.orgunits.contains(&normalize_uuid(uuid)))
+        };
+        let opted_out_units = research
+            .managingOrganisationalUnit
+            .iter()
+            .filter(|u| opted_out_unit(u))
+            .count()
+            + research
+                .organisationalUnits
+                .as_deref()
+                .unwrap_or(&[])
+                .iter()
+                .filter(|u| opted_out_unit(u))
+                .count();
+        if opted_out_persons == 0 && opted_out_units == 0 {
+            return true;
+        }
+
+        match self.rule {
+            OptOutRule::DropResearch => {
+                info!("Dropping research {} with opt-outs.", uuid);
+                false
+            }
+            OptOutRule::RedactPerson => {
+                info!(
+                    "Removing {} opt-out persons and {} orgunits from research {}.",
+                    opted_out_persons, opted_out_units, uuid
+                );
+                if let Some(associations) = research.personAssociations.as_mut() {
+                    associations.retain(|a| !self.is_association_opted_out(a));
+                }
+                if research
+                    .managingOrganisationalUnit
+                    .as_ref()
+                    .is_some_and(opted_out_unit)
+                {
+                    research.managingOrganisationalUnit = None;
+                }
+                if let Some(units) = research.organisationalUnits.as_mut() {
+                    units.retain(|u| !opted_out_unit(u));
+                }
+                true
+            }
+        }
+    }
+
+    /// Applies the policy to a person. Returns false if the person opted
+    /// out. Associations with opted-out orgunits are removed, or the
+    /// person is dropped with the drop rule.
+    pub fn apply_person(&self, person: &mut PersonJson) -> bool {
+        let name = person
+            .get_first_and_last_name()
+            .map(|(first, last)| format!("{} {}", first, last));
+        if self.is_person_opted_out(person.get_uuid(), name.as_deref())
+            || person
+                .get_name_variants()
+                .iter()
+                .any(|variant| self.is_person_opted_out(None, Some(variant)))
+        {
+            info!("Opt-out person {:?}.", person.get_uuid());
+            return false;
+        }
+
+        let Some(associations) = person.staffOrganisationAssociations.as_mut() else {
+            return true;
+        };
+        let opted_out_unit = |a: &crate::json_person::StaffOrganisationAssociation| {
+            a.organisationalUnit
+                .as_ref()
+                .and_then(|unit| unit.uuid.as_deref())
+                .is_some_and(|uuid| self.orgunits.contains(&normalize_uuid(uuid)))
+        };
+        if !associations.iter().any(opted_out_unit) {
+            return true;
+        }
+        match self.rule {
+            OptOutRule::DropResearch => false,
+            OptOutRule::RedactPerson => {
+                associations.retain(|a| !opted_out_unit(a));
+                true
+            }
+        }
+    }
+
+    // The identifiers to look for: the original uuids, their safe uuids
+    // if they are in the map (or can be derived), the ORCIDs and emails.
+    fn identifiers(&self, umap: &UuidMap) -> Vec<(&'static str, String)> {
+        let mut identifiers = vec![];
+        for (kind, uuids) in [
+            ("person", &self.persons),
+            ("research", &self.research),
+            ("orgunit", &self.orgunits),
+        ] {
+            for uuid in uuids {
+                identifiers.push((kind, uuid.clone()));
+                if let Some(safe_uuid) = umap.lookup(uuid) {
+                    identifiers.push(("safe uuid", safe_uuid));
+                }
+            }
+        }
+        identifiers.extend(self.orcids.iter().map(|orcid| ("orcid", orcid.clone())));
+        identifiers.extend(self.emails.iter().map(|email| ("email", email.clone())));
+        identifiers.sort();
+        identifiers
+    }
+
+    /// The scanner for the opted-out identifiers and names, see verify()
+    /// and VerifiedWriter.
+    pub fn scanner(&self, umap: &UuidMap) -> LeakScanner {
+        let mut names: Vec<String> = vec![];
+        for name in &self.names {
+            names.push(format!(" {} ", name));
+            let mut parts: Vec<&str> = name.split(' ').collect();
+            if parts.len() > 1 {
+                parts.rotate_right(1);
+                names.push(format!(" {} ", parts.join(" ")));
+            }
+        }
+        names.sort();
+        LeakScanner {
+            identifiers: self.identifiers(umap),
+            names,
+        }
+    }
+
+    /// Scans an output (any text format) for the opted-out uuids, their
+    /// safe uuids, ORCIDs, emails and the names (also as "Last First"). Returns all the
+    /// hits, an empty list is the proof that the output is clean.
+    pub fn verify<R: BufRead>(
+        &self,
+        reader: R,
+        umap: &UuidMap,
+    ) -> Result<Vec<Leak>, std::io::Error> {
+        let scanner = self.scanner(umap);
+        let mut leaks = vec![];
+        for (line_number, line) in reader.lines().enumerate() {
+            leaks.extend(scanner.scan_line(line_number + 1, &line?));
+        }
+        Ok(leaks)
+    }
+}
+
+/// The identifiers and names to look for, prepared once by
+/// OptOutPolicy::scanner().
+#[derive(Debug)]
+pub struct LeakScanner {
+    identifiers: Vec<(&'static str, String)>,
+    // Folded, with a space on both sides to match whole words.
+    names: Vec<String>,
+}
+
+impl LeakScanner {
+    /// The opt-outs in one line of output.
+    pub fn scan_line(&self, line_number: usize, line: &str) -> Vec<Leak> {
+        let mut leaks = vec![];
+        let lowercase = line.to_lowercase();
+        for (kind, identifier) in &self.identifiers {
+            if lowercase.contains(identifier.as_str()) {
+                leaks.push(Leak {
+                    line: line_number,
+                    kind,
+                    identifier: identifier.clone(),
+                });
+            }
+        }
+        if self.names.is_empty() {
+            return leaks;
+        }
+        let folded = format!(" {} ", fold_text(line));
+        for name in &self.names {
+            if folded.contains(name.as_str()) {
+                leaks.push(Leak {
+                    line: line_number,
+                    kind: "name",
+                    identifier: name.trim().to_string(),
+                });
+            }
+        }
+        leaks
+    }
+}
+
+/// Checks an output line by line before it goes to the inner writer, so
+/// it can be streamed. A line with opt-outs is not written: the leaks are
+/// kept (see leaks()), and this and every later write fail. The lines
+/// before it have been written already.
+#[derive(Debug)]
+pub struct VerifiedWriter<W: Write> {
+    inner: W,
+    scanner: LeakScanner,
+    // The start of a line which is not complete yet.
+    pending: Vec<u8>,
+    line_number: usize,
+    leaks: Vec<Leak>,
+}
+
+impl<W: Write> VerifiedWriter<W> {
+    pub fn new(inner: W, policy: &OptOutPolicy, umap: &UuidMap) -> Self {
+        VerifiedWriter {
+            inner,
+            scanner: policy.scanner(umap),
+            pending: vec![],
+            line_number: 0,
+            leaks: vec![],
+        }
+    }
+
+    pub fn leaks(&self) -> &[Leak] {
+        &self.leaks
+    }
+
+    // Checks a line (with its newline, if any) and writes it.
+    fn write_line(&mut self, line: &[u8]) -> std::io::Result<()> {
+        self.line_number += 1;
+        let text = String::from_utf8_lossy(line);
+        let leaks = self.scanner.scan_line(self.line_number, text.trim_end());
+        if !leaks.is_empty() {
+            self.leaks = leaks;
+            return Err(std::io::Error::other("opt-outs found in the output"));
+        }
+        self.inner.write_all(line)
+    }
+
+    /// Checks and writes the last line if it has no newline, and flushes.
+    pub fn finish(&mut self) -> std::io::Result<()> {
+        if !self.leaks.is_empty() {
+            return Err(std::io::Error::other("opt-outs found in the output"));
+        }
+        if !self.pending.is_empty() {
+            let line = std::mem::take(&mut self.pending);
+            self.write_line(&line)?;
+        }
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Write for VerifiedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if !self.leaks.is_empty() {
+            return Err(std::io::Error::other("opt-outs found in the output"));
+        }
+        self.pending.extend_from_slice(buf);
+        while let Some(end) = self.pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            self.write_line(&line)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// ===========================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERSON: &str = "6ed28bcf-7127-4ad9-8120-96eecf60f57c";
+
+    fn make_research() -> ResearchJson {
+        let data = r#"{
+            "uuid": "00000000-0000-0000-0000-000000000001",
+            "managingOrganisationalUnit": {"uuid": "a0000000-0000-0000-0000-000000000004"},
+            "personAssociations": [
+                {"name": {"firstName": "Peter", "lastName": "Berck"},
+                 "person": {"uuid": "01234567-0123-0123-0123-0123456789ab"}},
+                {"name": {"firstName": "Åsa", "lastName": "Björk"},
+                 "person": {"uuid": "6ed28bcf-7127-4ad9-8120-96eecf60f57c"}},
+                {"name": {"firstName": "Quinten", "lastName": "Berck"}}
+            ]
+        }"#;
+        serde_json::from_str(data).expect("Failed to parse research.")
+    }
+
+    fn make_policy(rule: OptOutRule) -> OptOutPolicy {
+        let mut policy = OptOutPolicy::new(rule);
+        for line in [
+            "# comment",
+            PERSON,
+            "name: Berck, Quinten",
+            "orgunit:a0000000-0000-0000-0000-000000000004",
+        ] {
+            policy.add_line(line).expect("Failed to add line.");
+        }
+        assert!(policy.add_line("research:not-a-uuid").is_err());
+        assert!(policy.add_line("colour:red").is_err());
+        policy
+    }
+
+    #[test]
+    fn test_read_rejects_bad_lines() {
+        let path = std::env::temp_dir().join(format!("lucris_test_optout_{}.txt", Uuid::new_v4()));
+        let path = path.to_str().expect("Invalid path.");
+
+        std::fs::write(path, format!("# Opt-outs\n\n{}\nname:Åsa Björk\n", PERSON)).unwrap();
+        let policy = OptOutPolicy::read(path, OptOutRule::default()).expect("Valid file.");
+        assert_eq!(policy.len(), 2);
+
+        // A bare ORCID, without "orcid:".
+        std::fs::write(path, format!("{}\n0000-0002-1825-0097\n", PERSON)).unwrap();
+        let error = OptOutPolicy::read(path, OptOutRule::default()).unwrap_err();
+        assert!(matches!(error, LucrisError::Invalid { line: 2, .. }));
+        assert!(error.to_string().starts_with(&format!("{}:2: ", path)));
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_apply_research() {
+        let policy = make_policy(OptOutRule::RedactPerson);
+        assert_eq!(policy.len(), 3);
+        let mut research = make_research();
+        assert!(policy.apply_research(&mut research));
+        // Only Peter is left, also the one without uuid is gone.
+        assert_eq!(research.get_names(), vec!["Peter Berck"]);
+        assert!(research.managingOrganisationalUnit.is_none());
+
+        let policy = make_policy(OptOutRule::DropResearch);
+        assert!(!policy.apply_research(&mut make_research()));
+
+        let mut policy = OptOutPolicy::default();
+        policy
+            .add_line("research:00000000-0000-0000-0000-000000000001")
+            .unwrap();
+        assert!(!policy.apply_research(&mut make_research()));
+    }
+
+    #[test]
+    fn test_verify() {
+        let policy = make_policy(OptOutRule::RedactPerson);
+        let mut umap = UuidMap::new();
+        let safe_uuid = umap.add_uuid(PERSON);
+        let output = format!(
+            "{{\"persons\":[\"Peter Berck\"]}}\n{{\"uuid\":\"{}\"}}\nBerck, Quinten\n{}\n",
+            safe_uuid,
+            PERSON.to_uppercase()
+        );
+        let leaks = policy
+            .verify(output.as_bytes(), &umap)
+            .expect("Failed to read.");
+        let found: Vec<(usize, &str)> = leaks.iter().map(|l| (l.line, l.kind)).collect();
+        assert_eq!(found, vec![(2, "safe uuid"), (3, "name"), (4, "person")]);
+    }
+
+    #[test]
+    fn test_uppercase_uuid() {
+        let mut policy = OptOutPolicy::default();
+        policy
+            .add_line(&format!("person:{}", PERSON.to_uppercase()))
+            .unwrap();
+        let mut research = make_research();
+        assert!(policy.apply_research(&mut research));
+        assert_eq!(research.get_names(), vec!["Peter Berck", "Quinten Berck"]);
+
+        // The verification finds the safe uuid, and the learned name.
+        let person: PersonJson = serde_json::from_str(&format!(
+            r#"{{"uuid": "{}", "name": {{"firstName": "Åsa", "lastName": "Björk"}}}}"#,
+            PERSON
+        ))
+        .expect("Failed to parse person.");
+        policy.learn_person(&person);
+        let mut umap = UuidMap::new();
+        let safe_uuid = umap.add_uuid(PERSON);
+        let output = format!("{{\"uuid\":\"{}\"}}\nÅsa Björk\n", safe_uuid);
+        let leaks = policy
+            .verify(output.as_bytes(), &umap)
+            .expect("Failed to read.");
+        let found: Vec<(usize, &str)> = leaks.iter().map(|l| (l.line, l.kind)).collect();
+        assert_eq!(found, vec![(1, "safe uuid"), (2, "name")]);
+    }
+
+    #[test]
+    fn test_verified_writer() {
+        let policy = make_policy(OptOutRule::RedactPerson);
+        let umap = UuidMap::new();
+        let mut writer = VerifiedWriter::new(vec![], &policy, &umap);
+        write!(writer, "Peter Berck\nMemory-based").unwrap();
+        writeln!(writer, " language models").unwrap();
+        assert!(writeln!(writer, "Quinten Berck").is_err());
+        assert!(writeln!(writer, "Peter Berck").is_err());
+        assert!(writer.finish().is_err());
+        let found: Vec<(usize, &str)> = writer.leaks().iter().map(|l| (l.line, l.kind)).collect();
+        assert_eq!(found, vec![(3, "name")]);
+        assert_eq!(writer.inner, b"Peter Berck\nMemory-based language models\n");
+
+        let mut writer = VerifiedWriter::new(vec![], &policy, &umap);
+        write!(writer, "Peter Berck").unwrap();
+        writer.finish().expect("Clean output.");
+        assert_eq!(writer.inner, b"Peter Berck");
+    }
+
+    #[test]
+    fn test_learn_person() {
+        let data = r#"{
+            "uuid": "6ed28bcf-7127-4ad9-8120-96eecf60f57c",
+            "name": {"firstName": "Åsa", "lastName": "Björk"},
+            "orcid": "https://orcid.org/0000-0002-1825-0097",
+            "staffOrganisationAssociations": [
+                {"emails": [{"value": {"value": "asa.bjork@example.org"}}]}
+            ]
+        }"#;
+        let person: PersonJson = serde_json::from_str(data).expect("Failed to parse person.");
+        let mut policy = OptOutPolicy::default();
+        policy.add_person(PERSON);
+        policy.learn_person(&person);
+        assert_eq!(policy.len(), 4);
+
+        let output = "Bjork, Asa\nASA.BJORK@example.org\n0000-0002-1825-0097\nÅsa Björkman\n";
+        let leaks = policy
+            .verify(output.as_bytes(), &UuidMap::new())
+            .expect("Failed to read.");
+        let found: Vec<(usize, &str)> = leaks.iter().map(|l| (l.line, l.kind)).collect();
+        // The email address also contains the name.
+        assert_eq!(
+            found,
+            vec![(1, "name"), (2, "email"), (2, "name"), (3, "orcid")]
+        );
+    }
+}
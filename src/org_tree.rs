@@ -0,0 +1,452 @@
+use crate::json_orgunits::{LocaleText, OrgUnitJson};
+use log::{debug, info, warn};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// The organisational-unit hierarchy from orgunits.jsonl. Units can have
+// more than one parent (reorganisations), closed units (a period with an
+// endDate in the past, or a startDate in the future) are only used as
+// parent when there is no open one.
+// The uuids are the original LUCRIS orgunit uuids, these are not personal
+// data and are not translated to safe uuids.
+
+/// One unit in the tree.
+#[derive(Debug, Clone)]
+pub struct OrgUnit {
+    pub uuid: String,
+    pub name: String,
+    // Last part of the type uri, e.g. "faculty" or "department".
+    pub unit_type: String,
+    pub parents: Vec<String>,
+    // First ten characters of period.startDate and period.endDate
+    // (YYYY-MM-DD).
+    pub start_date: Option<String>,
+    pub end_date: Option<String>,
+}
+
+/// The position of a unit in the tree, as it ends up in the output. The
+/// path runs from the top (the university) down to the unit itself.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct OrgPath {
+    pub uuid: String,
+    pub path: Vec<String>,
+    pub faculty: Option<String>,
+}
+
+impl OrgPath {
+    /// A unit which is not (or not yet) looked up in the tree.
+    pub fn unresolved(uuid: &str, name: Option<&str>) -> Self {
+        OrgPath {
+            uuid: uuid.to_string(),
+            path: name.map(String::from).into_iter().collect(),
+            faculty: None,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        self.path.last().map(|name| name.as_str()).unwrap_or("")
+    }
+}
+
+#[derive(Debug)]
+pub struct OrgTree {
+    units: HashMap<String, OrgUnit>,
+    children: HashMap<String, Vec<String>>,
+    // Units which have ended before, or start after, this date are closed.
+    reference_date: String,
+}
+
+impl Default for OrgTree {
+    fn default() -> Self {
+        Self {
+            units: HashMap::new(),
+            children: HashMap::new(),
+            reference_date: today(),
+        }
+    }
+}
+
+// Today as YYYY-MM-DD, without pulling in a date crate. This is the
+// civil_from_days algorithm from Howard Hinnant.
+pub(crate) fn today() -> String {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() / 86400)
+        .unwrap_or(0) as i64;
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+fn pick_locale(texts: &[LocaleText], locale: &str) -> Option<String> {
+    let pick = |loc: &str| {
+        texts
+            .iter()
+            .find(|lt| lt.locale.as_deref() == Some(loc))
+            .and_then(|lt| lt.value.clone())
+    };
+    pick(locale)
+        .or_else(|| pick("en_GB"))
+        .or_else(|| texts.iter().find_map(|lt| lt.value.clone()))
+}
+
+impl OrgTree {
+    pub fn new(orgunits: &[OrgUnitJson], locale: &str) -> Self {
+        let mut units = HashMap::new();
+        for unit in orgunits {
+            let Some(uuid) = unit.uuid.as_ref() else {
+                warn!("Orgunit without uuid.");
+                continue;
+            };
+            let name = unit
+                .name
+                .as_ref()
+                .and_then(|name| name.text.as_deref())
+                .and_then(|texts| pick_locale(texts, locale))
+                .unwrap_or_default();
+            let unit_type = unit
+                .type_field
+                .as_ref()
+                .and_then(|t| t.uri.as_deref())
+                .and_then(|uri| uri.rsplit('/').next())
+                .unwrap_or("")
+                .to_string();
+            let parents = unit
+                .parents
+                .as_deref()
+                .unwrap_or(&[])
+                .iter()
+                .filter_map(|p| p.uuid.clone())
+                .filter(|p| p != uuid)
+                .collect();
+            let start_date = unit
+                .period
+                .as_ref()
+                .and_then(|p| p.startDate.as_deref())
+                .map(|d| d.chars().take(10).collect());
+            let end_date = unit
+                .period
+                .as_ref()
+                .and_then(|p| p.endDate.as_deref())
+                .map(|d| d.chars().take(10).collect());
+            units.insert(
+                uuid.clone(),
+                OrgUnit {
+                    uuid: uuid.clone(),
+                    name,
+                    unit_type,
+                    parents,
+                    start_date,
+                    end_date,
+                },
+            );
+        }
+
+        let mut children: HashMap<String, Vec<String>> = HashMap::new();
+        for unit in units.values() {
+            for parent in &unit.parents {
+                children
+                    .entry(parent.clone())
+                    .or_default()
+                    .push(unit.uuid.clone());
+            }
+        }
+        for c in children.values_mut() {
+            c.sort();
+        }
+        info!("Orgunit tree with {} units.", units.len());
+
+        Self {
+            units,
+            children,
+            reference_date: today(),
+        }
+    }
+
+    /// Sets the date used to decide if a unit is closed (YYYY-MM-DD).
+    /// It is today by default.
+    pub fn set_reference_date(&mut self, date: &str) {
+        self.reference_date = date.to_string();
+    }
+
+    pub fn reference_date(&self) -> &str {
+        &self.reference_date
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.units.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.units.len()
+    }
+
+    pub fn get(&self, uuid: &str) -> Option<&OrgUnit> {
+        self.units.get(uuid)
+    }
+
+    /// Whether the unit is not open at the reference date: it has ended
+    /// before it, or it has not started yet.
+    pub fn is_closed(&self, uuid: &str) -> bool {
+        let Some(unit) = self.units.get(uuid) else {
+            return false;
+        };
+        let date = self.reference_date.as_str();
+        unit.end_date.as_deref().is_some_and(|end| end < date)
+            || unit.start_date.as_deref().is_some_and(|start| start > date)
+    }
+
+    // The parent we follow upwards: the first open one, or the first one
+    // if they are all closed.
+    fn parent(&self, unit: &OrgUnit) -> Option<&OrgUnit> {
+        let known: Vec<&OrgUnit> = unit
+            .parents
+            .iter()
+            .filter_map(|p| self.units.get(p))
+            .collect();
+        known
+            .iter()
+            .find(|p| !self.is_closed(&p.uuid))
+            .or(known.first())
+            .copied()
+    }
+
+    /// The ancestors of a unit, the closest first. Stops at cycles.
+    pub fn ancestors(&self, uuid: &str) -> Vec<&OrgUnit> {
+        let mut ancestors = vec![];
+        let mut seen: HashSet<&str> = HashSet::from([uuid]);
+        let mut current = self.units.get(uuid);
+        while let Some(parent) = current.and_then(|unit| self.parent(unit)) {
+            if !seen.insert(&parent.uuid) {
+                warn!("Cycle in the orgunit tree at {}.", parent.uuid);
+                break;
+            }
+            ancestors.push(parent);
+            current = Some(parent);
+        }
+        ancestors
+    }
+
+    /// The faculty of a unit (the unit itself or an ancestor).
+    pub fn faculty(&self, uuid: &str) -> Option<&OrgUnit> {
+        self.units
+            .get(uuid)
+            .into_iter()
+            .chain(self.ancestors(uuid))
+            .find(|unit| unit.unit_type == "faculty")
+    }
+
+    /// The unit and all the units below it, following every parent link
+    /// (also the closed ones).
+    pub fn descendants(&self, uuid: &str) -> HashSet<String> {
+        let mut found = HashSet::from([uuid.to_string()]);
+        let mut todo = vec![uuid.to_string()];
+        while let Some(current) = todo.pop() {
+            for child in self.children.get(&current).into_iter().flatten() {
+                if found.insert(child.clone()) {
+                    todo.push(child.clone());
+                }
+            }
+        }
+        found
+    }
+
+    /// Looks up units on uuid, or on name (case insensitive).
+    pub fn find(&self, uuid_or_name: &str) -> Vec<&OrgUnit> {
+        let key = uuid_or_name.trim();
+        if let Some(unit) = self.units.get(key) {
+            return vec![unit];
+        }
+        let key = key.to_lowercase();
+        let mut found: Vec<&OrgUnit> = self
+            .units
+            .values()
+            .filter(|unit| unit.name.to_lowercase() == key)
+            .collect();
+        found.sort_by(|a, b| a.uuid.cmp(&b.uuid));
+        found
+    }
+
+    /// The uuids of the units (given as uuid or name) and everything below
+    /// them. Keys which are not in the tree are kept as they are, so a
+    /// plain uuid still matches without an orgunits file.
+    pub fn expand(&self, uuids_or_names: &[String]) -> HashSet<String> {
+        let mut expanded = HashSet::new();
+        for key in uuids_or_names {
+            let key = key.trim();
+            if key.is_empty() {
+                continue;
+            }
+            let found = self.find(key);
+            if found.is_empty() {
+                warn!("Orgunit {:?} not in the tree.", key);
+                expanded.insert(key.to_string());
+            }
+            for unit in found {
+                expanded.extend(self.descendants(&unit.uuid));
+            }
+        }
+        expanded
+    }
+
+    /// The OrgPath for a unit. Units which are not in the tree get a path
+    /// with only the name from the record (if there is one).
+    pub fn resolve(&self, uuid: &str, fallback_name: Option<&str>) -> OrgPath {
+        let Some(unit) = self.units.get(uuid) else {
+            debug!("Orgunit {} not in the tree.", uuid);
+            return OrgPath::unresolved(uuid, fallback_name);
+        };
+        let mut path: Vec<String> = self
+            .ancestors(uuid)
+            .iter()
+            .map(|unit| unit.name.clone())
+            .collect();
+        path.reverse();
+        path.push(unit.name.clone());
+        OrgPath {
+            uuid: uuid.to_string(),
+            path,
+            faculty: self.faculty(uuid).map(|unit| unit.name.clone()),
+        }
+    }
+}
+
+// ===========================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::Rejects;
+    use crate::json_orgunits::read_orgunits_jsonl;
+    use std::path::Path;
+
+    const LAB: &str = "0463eb96-7994-4860-a9b6-baff3ecf2ff6";
+    const CENTRE: &str = "a0000000-0000-0000-0000-000000000004";
+    const FACULTY: &str = "a0000000-0000-0000-0000-000000000002";
+
+    fn make_tree() -> OrgTree {
+        let data_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("data")
+            .join("orgunits.jsonl");
+        let orgunits = read_orgunits_jsonl(
+            data_path.to_str().expect("Invalid path."),
+            &mut Rejects::default(),
+        )
+        .expect("Failed to read orgunits.");
+        let mut tree = OrgTree::new(&orgunits, "en_GB");
+        tree.set_reference_date("2025-01-01");
+        tree
+    }
+
+    #[test]
+    fn test_today() {
+        let today = today();
+        assert_eq!(today.len(), 10);
+        assert!(today.as_str() > "2024-01-01");
+    }
+
+    #[test]
+    fn test_org_path_skips_closed_parent() {
+        let tree = make_tree();
+        assert_eq!(tree.len(), 7);
+        assert!(tree.is_closed("a0000000-0000-0000-0000-000000000003"));
+        let org_path = tree.resolve(LAB, None);
+        assert_eq!(
+            org_path.path,
+            vec![
+                "Lund University",
+                "Joint Faculties of Humanities and Theology",
+                "Lund University Humanities Lab"
+            ]
+        );
+        assert_eq!(
+            org_path.faculty.as_deref(),
+            Some("Joint Faculties of Humanities and Theology")
+        );
+        assert_eq!(org_path.get_name(), "Lund University Humanities Lab");
+
+        // Before the old faculty was closed.
+        let mut tree = make_tree();
+        tree.set_reference_date("1995-01-01");
+        assert_eq!(
+            tree.faculty(LAB).map(|unit| unit.name.as_str()),
+            Some("Faculty of Humanities (old)")
+        );
+    }
+
+    #[test]
+    fn test_not_yet_open_parent() {
+        // The parents of the lab in the other order, the new faculty first.
+        let data_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("data")
+            .join("orgunits.jsonl");
+        let mut orgunits = read_orgunits_jsonl(
+            data_path.to_str().expect("Invalid path."),
+            &mut Rejects::default(),
+        )
+        .expect("Failed to read orgunits.");
+        for unit in orgunits.iter_mut() {
+            if unit.uuid.as_deref() == Some(LAB) {
+                unit.parents.as_mut().expect("Lab has parents.").reverse();
+            }
+        }
+        let mut tree = OrgTree::new(&orgunits, "en_GB");
+        tree.set_reference_date("1995-01-01");
+        assert!(tree.is_closed(FACULTY));
+        assert_eq!(
+            tree.faculty(LAB).map(|unit| unit.name.as_str()),
+            Some("Faculty of Humanities (old)")
+        );
+        tree.set_reference_date("2025-01-01");
+        assert_eq!(
+            tree.faculty(LAB).map(|unit| unit.name.as_str()),
+            Some("Joint Faculties of Humanities and Theology")
+        );
+    }
+
+    #[test]
+    fn test_unknown_unit() {
+        let tree = make_tree();
+        let org_path = tree.resolve("unknown", Some("Somewhere"));
+        assert_eq!(org_path.path, vec!["Somewhere"]);
+        assert_eq!(org_path.faculty, None);
+    }
+
+    #[test]
+    fn test_descendants_and_find() {
+        let tree = make_tree();
+        let below = tree.descendants(FACULTY);
+        assert_eq!(below.len(), 4); // Faculty, lab, centre and division.
+        assert!(below.contains(CENTRE));
+        assert!(below.contains("a0000000-0000-0000-0000-000000000005"));
+        assert_eq!(
+            tree.find("centre for languages and literature")[0].uuid,
+            CENTRE
+        );
+        assert_eq!(tree.find(CENTRE)[0].uuid, CENTRE);
+        assert!(tree.find("No such unit").is_empty());
+    }
+
+    #[test]
+    fn test_expand() {
+        let tree = make_tree();
+        let expanded = tree.expand(&[
+            "Centre for Languages and Literature".to_string(),
+            "not-in-the-tree".to_string(),
+        ]);
+        assert_eq!(expanded.len(), 3);
+        assert!(expanded.contains(CENTRE));
+        assert!(expanded.contains("a0000000-0000-0000-0000-000000000005"));
+        assert!(expanded.contains("not-in-the-tree"));
+    }
+}
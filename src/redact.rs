@@ -0,0 +1,306 @@
+use crate::json_person::{PersonClean, PersonJson};
+use crate::json_research::ResearchClean;
+use clap::ValueEnum;
+use regex::{escape, Regex};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Write;
+
+// Removes personal information (emails, phone numbers, addresses) from the
+// abstracts and profile texts before export. Every span found is replaced
+// by a placeholder. The spans are found with regular expressions, and with
+// the known values from the persons file (the emails, phone numbers and
+// addresses in the staff associations), which catches the ones the
+// regular expressions miss.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, ValueEnum, Serialize)]
+pub enum PiiKind {
+    Email,
+    Phone,
+    Address,
+}
+
+impl PiiKind {
+    pub fn placeholder(&self) -> &'static str {
+        match self {
+            PiiKind::Email => "[EMAIL]",
+            PiiKind::Phone => "[PHONE]",
+            PiiKind::Address => "[ADDRESS]",
+        }
+    }
+}
+
+// Something with an @ and a domain, also with [at] instead of @.
+const EMAIL_PATTERN: &str =
+    r"(?i)\b[a-z0-9._%+-]+(?:@|\s?\[at\]\s?|\s?\(at\)\s?)[a-z0-9-]+(?:\.[a-z0-9-]+)*\.[a-z]{2,}\b";
+// International (+46 46 222 12 34, +46 (0)46-2221234) or national with a
+// leading zero (046-222 12 34). Years and page ranges do not start with 0.
+const PHONE_PATTERN: &str = r"(?:\+|\b00)\d{1,3}[\s-]?(?:\(0\)\s?)?\d{1,4}(?:[\s-]?\d{2,4}){1,4}\b|\b0\d{1,3}[\s-]\d{2,4}(?:[\s-]?\d{2,3}){1,3}\b";
+// Swedish postal code with city (221 00 Lund) or a post box (Box 117).
+const ADDRESS_PATTERN: &str = r"\b(?:\d{3}\s\d{2}\s+\p{Lu}\p{Ll}+|Box\s+\d+)\b";
+
+/// The number of replacements per kind in one text or record.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize)]
+pub struct RedactCounts {
+    pub email: usize,
+    pub phone: usize,
+    pub address: usize,
+}
+
+impl RedactCounts {
+    fn add(&mut self, kind: PiiKind, count: usize) {
+        match kind {
+            PiiKind::Email => self.email += count,
+            PiiKind::Phone => self.phone += count,
+            PiiKind::Address => self.address += count,
+        }
+    }
+
+    fn merge(&mut self, other: RedactCounts) {
+        self.email += other.email;
+        self.phone += other.phone;
+        self.address += other.address;
+    }
+
+    pub fn total(&self) -> usize {
+        self.email + self.phone + self.address
+    }
+}
+
+/// The redactions in one record, see write_tsv().
+#[derive(Debug, Serialize, PartialEq)]
+pub struct RedactionEntry {
+    pub uuid: String,
+    // "research" or "person".
+    pub record: &'static str,
+    pub counts: RedactCounts,
+}
+
+#[derive(Debug)]
+pub struct Redactor {
+    // Known values first, they are more specific than the patterns.
+    patterns: Vec<(PiiKind, Regex)>,
+}
+
+// A regex for the known phone number which also matches it with other
+// spacing and dashes.
+fn phone_regex(phone: &str) -> Option<String> {
+    let digits: Vec<String> = phone
+        .chars()
+        .filter(|c| c.is_ascii_digit())
+        .map(String::from)
+        .collect();
+    // Short numbers (extensions) would match too much.
+    if digits.len() < 6 {
+        return None;
+    }
+    let plus = if phone.trim_start().starts_with('+') {
+        r"\+"
+    } else {
+        ""
+    };
+    Some(format!(r"{}{}\b", plus, digits.join(r"[\s\-()]*")))
+}
+
+impl Redactor {
+    /// A redactor for the given kinds. The known values are (kind, value)
+    /// pairs, see known_values(). Values of kinds which are not redacted
+    /// are ignored.
+    pub fn new(kinds: &[PiiKind], known: &[(PiiKind, String)]) -> Result<Self, regex::Error> {
+        let mut patterns = vec![];
+        for &kind in kinds {
+            let mut values: Vec<String> = known
+                .iter()
+                .filter(|(known_kind, _)| *known_kind == kind)
+                .filter_map(|(_, value)| match kind {
+                    PiiKind::Phone => phone_regex(value),
+                    _ => Some(escape(value.trim())).filter(|value| value.len() > 3),
+                })
+                .collect();
+            values.sort();
+            values.dedup();
+            if !values.is_empty() {
+                let pattern = format!(r"(?i)(?:{})", values.join("|"));
+                patterns.push((kind, Regex::new(&pattern)?));
+            }
+        }
+        for &kind in kinds {
+            let pattern = match kind {
+                PiiKind::Email => EMAIL_PATTERN,
+                PiiKind::Phone => PHONE_PATTERN,
+                PiiKind::Address => ADDRESS_PATTERN,
+            };
+            patterns.push((kind, Regex::new(pattern)?));
+        }
+        Ok(Redactor { patterns })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Replaces the personal information in the text by placeholders.
+    pub fn redact(&self, text: &str) -> (String, RedactCounts) {
+        let mut text = text.to_string();
+        let mut counts = RedactCounts::default();
+        for (kind, regex) in &self.patterns {
+            let count = regex.find_iter(&text).count();
+            if count > 0 {
+                counts.add(*kind, count);
+                text = regex.replace_all(&text, kind.placeholder()).into_owned();
+            }
+        }
+        (text, counts)
+    }
+}
+
+/// The emails, phone numbers and addresses of a person, to be used as
+/// known values in Redactor::new().
+pub fn known_values(person: &PersonJson) -> Vec<(PiiKind, String)> {
+    let mut known = vec![];
+    for email in person.get_emails() {
+        known.push((PiiKind::Email, email.to_string()));
+    }
+    for phone in person.get_phone_numbers() {
+        known.push((PiiKind::Phone, phone.to_string()));
+    }
+    for address in person.get_addresses() {
+        known.push((PiiKind::Address, address.to_string()));
+    }
+    known
+}
+
+// Redacts the text in place, and adds the replacements to the counts.
+fn redact_text(redactor: &Redactor, text: &mut Option<String>, counts: &mut RedactCounts) {
+    let Some(text) = text else {
+        return;
+    };
+    let (redacted, text_counts) = redactor.redact(text);
+    if text_counts.total() > 0 {
+        *text = redacted;
+        counts.merge(text_counts);
+    }
+}
+
+/// Redacts the abstracts, also the translations. Returns an entry for
+/// every research output with redactions.
+pub fn redact_research(
+    research: &mut HashMap<String, ResearchClean>,
+    redactor: &Redactor,
+) -> Vec<RedactionEntry> {
+    let mut entries = vec![];
+    for (uuid, item) in research.iter_mut() {
+        let mut counts = RedactCounts::default();
+        redact_text(redactor, &mut item.abstract_text, &mut counts);
+        for texts in item.translations.values_mut() {
+            redact_text(redactor, &mut texts.abstract_text, &mut counts);
+        }
+        if counts.total() > 0 {
+            entries.push(RedactionEntry {
+                uuid: uuid.clone(),
+                record: "research",
+                counts,
+            });
+        }
+    }
+    entries.sort_by(|a, b| a.uuid.cmp(&b.uuid));
+    entries
+}
+
+/// Redacts the profile texts, also the translations. Returns an entry for
+/// every person with redactions.
+pub fn redact_persons(
+    persons: &mut HashMap<String, PersonClean>,
+    redactor: &Redactor,
+) -> Vec<RedactionEntry> {
+    let mut entries = vec![];
+    for (uuid, person) in persons.iter_mut() {
+        let mut counts = RedactCounts::default();
+        let (profile_info, translations) = person.get_profile_texts_mut();
+        redact_text(redactor, profile_info, &mut counts);
+        for texts in translations.values_mut() {
+            redact_text(redactor, &mut texts.profile_info, &mut counts);
+        }
+        if counts.total() > 0 {
+            entries.push(RedactionEntry {
+                uuid: uuid.clone(),
+                record: "person",
+                counts,
+            });
+        }
+    }
+    entries.sort_by(|a, b| a.uuid.cmp(&b.uuid));
+    entries
+}
+
+/// The counts per record as tab separated values, with a header.
+pub fn write_tsv<W: Write>(entries: &[RedactionEntry], output: W) -> Result<(), csv::Error> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(b'\t')
+        .from_writer(output);
+    writer.write_record(["uuid", "record", "email", "phone", "address"])?;
+    for entry in entries {
+        writer.write_record([
+            entry.uuid.clone(),
+            entry.record.to_string(),
+            entry.counts.email.to_string(),
+            entry.counts.phone.to_string(),
+            entry.counts.address.to_string(),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+// ===========================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [PiiKind; 3] = [PiiKind::Email, PiiKind::Phone, PiiKind::Address];
+
+    #[test]
+    fn test_redact_patterns() {
+        let redactor = Redactor::new(&ALL, &[]).expect("Invalid regex.");
+        let (text, counts) = redactor.redact(
+            "Mail peter.berck@humlab.lu.se or p.berck [at] gmail.com, \
+             call +46 46 222 12 34 or 046-222 12 34. \
+             Box 117, 221 00 Lund. Published 1990-2010, pages 112-134.",
+        );
+        assert_eq!(
+            text,
+            "Mail [EMAIL] or [EMAIL], call [PHONE] or [PHONE]. \
+             [ADDRESS], [ADDRESS]. Published 1990-2010, pages 112-134."
+        );
+        assert_eq!(
+            counts,
+            RedactCounts {
+                email: 2,
+                phone: 2,
+                address: 2
+            }
+        );
+
+        // Only the configured kinds.
+        let redactor = Redactor::new(&[PiiKind::Email], &[]).expect("Invalid regex.");
+        let (text, counts) = redactor.redact("peter@example.org, 046-222 12 34");
+        assert_eq!(text, "[EMAIL], 046-222 12 34");
+        assert_eq!(counts.total(), 1);
+    }
+
+    #[test]
+    fn test_redact_known_values() {
+        let known = vec![
+            (PiiKind::Phone, "+46 46 2221234".to_string()),
+            (PiiKind::Address, "Helgonabacken 12".to_string()),
+            (PiiKind::Phone, "1234".to_string()),
+        ];
+        let redactor = Redactor::new(&ALL, &known).expect("Invalid regex.");
+        let (text, counts) =
+            redactor.redact("At helgonabacken 12, phone +46-46-222 12 34, room 1234.");
+        assert_eq!(text, "At [ADDRESS], phone [PHONE], room 1234.");
+        assert_eq!(counts.phone, 1);
+        assert_eq!(counts.address, 1);
+    }
+}
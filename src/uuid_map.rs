@@ -1,10 +1,13 @@
-use log::warn;
+use log::{info, warn};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt;
 use std::fs::File;
-use std::io::BufRead;
 use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
 
 // Maybe use: https://docs.rs/short-uuid/latest/short_uuid/
@@ -20,16 +23,31 @@ use uuid::Uuid;
 
 /// Struct that holds the Hashmap converting original String uuids
 /// to the "safe" Uuids.
+///
+/// Only the `uuids` are saved to/loaded from a mapping file. The opt-outs
+/// are not part of the map, see OptOutPolicy. If a namespace is
+/// set (derived from a secret salt), new safe uuids are derived from the
+/// original uuid (v5) instead of being random (v4).
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct UuidMap {
     uuids: HashMap<String, Uuid>,
-    optout: Vec<String>,
-    // reverse mapping?
+    #[serde(skip)]
+    namespace: Option<Uuid>,
+    // Safe uuid -> original uuid, rebuilt when loading. Only used by
+    // resolve(), never by the exports.
+    #[serde(skip)]
+    reverse: HashMap<Uuid, String>,
 }
 
 impl fmt::Display for UuidMap {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}/{}", self.uuids.len(), self.optout.len())
+        write!(f, "{}", self.uuids.len())
+    }
+}
+
+impl Default for UuidMap {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -37,32 +55,116 @@ impl UuidMap {
     pub fn new() -> Self {
         Self {
             uuids: HashMap::new(),
-            optout: vec![],
+            namespace: None,
+            reverse: HashMap::new(),
+        }
+    }
+
+    /// Creates a map in keyed mode. The salt is turned into a namespace
+    /// uuid, and every safe uuid is derived from the namespace and the
+    /// original uuid. The same salt always gives the same pseudonyms.
+    pub fn with_salt(salt: &[u8]) -> Self {
+        let mut map = Self::new();
+        map.set_salt(salt);
+        map
+    }
+
+    pub fn set_salt(&mut self, salt: &[u8]) {
+        self.namespace = Some(Uuid::new_v5(&Uuid::NAMESPACE_OID, salt));
+    }
+
+    pub fn is_keyed(&self) -> bool {
+        self.namespace.is_some()
+    }
+
+    /// Reads the salt from a file. Surrounding whitespace (the trailing
+    /// newline most editors add) is ignored.
+    pub fn read_salt(&mut self, file_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let salt = std::fs::read(file_path)?;
+        let salt = salt.trim_ascii();
+        if salt.is_empty() {
+            return Err(format!("Empty salt file: {}", file_path).into());
+        }
+        self.set_salt(salt);
+        Ok(())
+    }
+
+    /// Loads a mapping saved by `save()`. The salt is not part of the file
+    /// and has to be set again.
+    pub fn load(file_path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let file = File::open(file_path)?;
+        let reader = BufReader::new(file);
+        let mut map: UuidMap = serde_json::from_reader(reader)?;
+        map.reverse = map
+            .uuids
+            .iter()
+            .map(|(uuid, safe_uuid)| (*safe_uuid, uuid.clone()))
+            .collect();
+        Ok(map)
+    }
+
+    /// Loads the mapping if the file exists, otherwise returns an empty map.
+    /// Used on the first run, when there is no mapping file yet.
+    pub fn load_or_new(file_path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        if Path::new(file_path).exists() {
+            Self::load(file_path)
+        } else {
+            warn!("Mapping file {} not found, starting a new one.", file_path);
+            Ok(Self::new())
+        }
+    }
+
+    /// Saves the mapping. It is written to a temporary file next to it
+    /// first, and then renamed over the old one, so a crash or a full disk
+    /// halfway does not lose the existing mapping.
+    pub fn save(&self, file_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_path = format!("{}.{}.tmp", file_path, std::process::id());
+        let result = self.write_to(&tmp_path);
+        if result.is_err() {
+            let _ = std::fs::remove_file(&tmp_path);
+            return result;
         }
+        std::fs::rename(&tmp_path, file_path)?;
+        Ok(())
+    }
+
+    fn write_to(&self, file_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let file = File::create(file_path)?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+        Ok(())
     }
 
     /// Adds a new String uuid and returns a new safe_uuid
-    /// as String.
+    /// as String. An existing mapping is never replaced, otherwise
+    /// the safe uuids would change between runs.
     pub fn add_uuid(&mut self, uuid: &str) -> String {
-        if self.uuids.contains_key(uuid) {
-            eprintln!("Repeating research uuid: {}", uuid);
+        if let Some(safe_uuid) = self.uuids.get(uuid) {
+            warn!("Repeating research uuid: {}", uuid);
+            return safe_uuid.to_string();
         }
-        let safe_uuid = Uuid::new_v4();
+        let safe_uuid = match &self.namespace {
+            Some(namespace) => Uuid::new_v5(namespace, uuid.as_bytes()),
+            None => Uuid::new_v4(),
+        };
         self.uuids.insert(uuid.to_string(), safe_uuid);
+        self.reverse.insert(safe_uuid, uuid.to_string());
         safe_uuid.to_string()
         //uuid.to_string() //// JUST FOR TESTING; KEEP SAME UUID
     }
 
-    pub fn add_optout_uuid(&mut self, uuid: &str) {
-        if !self.optout.iter().any(|x| *x == uuid) {
-            self.optout.push(uuid.to_string());
+    /// The safe uuid for a uuid, without adding it. In keyed mode the
+    /// safe uuid is derived if it is not in the map.
+    pub fn lookup(&self, uuid: &str) -> Option<String> {
+        match (self.uuids.get(uuid), &self.namespace) {
+            (Some(safe_uuid), _) => Some(safe_uuid.to_string()),
+            (None, Some(namespace)) => Some(Uuid::new_v5(namespace, uuid.as_bytes()).to_string()),
+            (None, None) => None,
         }
     }
 
-    pub fn optout_contains(&self, uuid: &str) -> bool {
-        self.optout.iter().any(|x| *x == uuid)
-    }
-
     /// Tries to look-up the uuid and return it. If the uuid is
     /// not present in the hashmap, it will be added.
     pub fn get_uuid_as_str(&mut self, uuid: &str) -> String {
@@ -77,22 +179,108 @@ impl UuidMap {
         self.uuids.len()
     }
 
-    pub fn read_optouts(&mut self, file_path: &str) -> Result<usize, Box<dyn std::error::Error>> {
-        let file = File::open(file_path)?;
-        let reader = BufReader::new(file);
-        let mut count: usize = 0;
-
-        reader
-            .lines()
-            .map_while(Result::ok)
-            .for_each(|line: String| {
-                if Uuid::parse_str(&line).is_ok() {
-                    self.add_optout_uuid(&line);
-                    count += 1;
-                } else {
-                    warn!("Skipping invalid opt-out UUID: {}", line);
-                }
-            });
-        Ok(count)
+    /// Looks up the original uuid for a safe uuid. Use resolve_audited()
+    /// when resolving on behalf of someone.
+    pub fn resolve(&self, safe_uuid: &str) -> Option<&str> {
+        let safe_uuid = Uuid::parse_str(safe_uuid.trim()).ok()?;
+        self.reverse.get(&safe_uuid).map(|uuid| uuid.as_str())
+    }
+
+    /// Same as resolve(), but writes an audit line for each lookup, also
+    /// for the ones which are not found. The line contains the time (unix
+    /// seconds), the user, the safe uuid and the original uuid (or "-").
+    pub fn resolve_audited(
+        &self,
+        safe_uuid: &str,
+        user: &str,
+        audit: &mut impl Write,
+    ) -> Result<Option<&str>, std::io::Error> {
+        let original = self.resolve(safe_uuid);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        writeln!(
+            audit,
+            "{}\t{}\t{}\t{}",
+            now,
+            user,
+            safe_uuid.trim(),
+            original.unwrap_or("-")
+        )?;
+        info!("{} resolved {} -> {:?}", user, safe_uuid.trim(), original);
+        Ok(original)
+    }
+}
+
+// ===========================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_load_roundtrip() {
+        let mut umap = UuidMap::new();
+        let safe_uuid = umap.add_uuid("01234567-0123-0123-0123-0123456789AB");
+
+        let path =
+            std::env::temp_dir().join(format!("lucris_test_uuidmap_{}.json", Uuid::new_v4()));
+        let path = path.to_str().expect("Invalid path.");
+        umap.save(path).expect("Failed to save map.");
+        // Saving again replaces the file.
+        umap.save(path).expect("Failed to save map.");
+
+        let mut loaded = UuidMap::load(path).expect("Failed to load map.");
+        assert_eq!(loaded.count(), 1);
+        assert_eq!(
+            loaded.resolve(&safe_uuid),
+            Some("01234567-0123-0123-0123-0123456789AB")
+        );
+        assert_eq!(
+            loaded.lookup("01234567-0123-0123-0123-0123456789AB"),
+            Some(safe_uuid.clone())
+        );
+        assert_eq!(
+            loaded.get_uuid_as_str("01234567-0123-0123-0123-0123456789AB"),
+            safe_uuid
+        );
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_resolve() {
+        let mut umap = UuidMap::new();
+        let safe_uuid = umap.add_uuid("01234567-0123-0123-0123-0123456789AB");
+        assert_eq!(
+            umap.resolve(&safe_uuid),
+            Some("01234567-0123-0123-0123-0123456789AB")
+        );
+        assert_eq!(umap.resolve("01234567-0123-0123-0123-0123456789AB"), None);
+        assert_eq!(umap.resolve("not a uuid"), None);
+
+        let mut audit = Vec::new();
+        let original = umap
+            .resolve_audited(&safe_uuid, "steward", &mut audit)
+            .expect("Failed to write audit line.");
+        assert_eq!(original, Some("01234567-0123-0123-0123-0123456789AB"));
+        let audit = String::from_utf8(audit).unwrap();
+        assert!(audit.ends_with(&format!(
+            "\tsteward\t{}\t01234567-0123-0123-0123-0123456789AB\n",
+            safe_uuid
+        )));
+    }
+
+    #[test]
+    fn test_keyed_is_deterministic() {
+        let uuid = "01234567-0123-0123-0123-0123456789AB";
+        let mut umap_a = UuidMap::with_salt(b"secret");
+        let mut umap_b = UuidMap::with_salt(b"secret");
+        let mut umap_c = UuidMap::with_salt(b"another secret");
+        let safe_a = umap_a.get_uuid_as_str(uuid);
+        assert_eq!(Some(safe_a.clone()), umap_b.lookup(uuid));
+        assert_eq!(safe_a, umap_b.get_uuid_as_str(uuid));
+        assert_ne!(safe_a, umap_c.get_uuid_as_str(uuid));
+        assert_ne!(safe_a, uuid.to_lowercase());
     }
 }